@@ -0,0 +1,125 @@
+// The SHA2 crypto extensions are only used on aarch64, where the `sha2` crate needs its assembly
+// (and a C compiler) to use them.
+#![cfg(target_arch = "aarch64")]
+
+use crate::compress::{compress_portable, K, PADDING_BLOCK};
+use crate::portable_impl::{state_to_digest, Engine};
+use crate::{have_sha_extensions, Sha256, Sha256Context, HASH_LEN, SHA256_IV};
+use alloc::vec::Vec;
+use core::arch::aarch64::*;
+
+/// Implementation of SHA256 using the ARMv8 SHA2 crypto extensions.
+///
+/// Falls back to the portable compression function on CPUs without the extensions.
+pub struct Armv8Sha2Impl;
+
+/// Streaming context for `Armv8Sha2Impl`.
+#[derive(Clone)]
+pub struct Armv8Sha2Context {
+    engine: Engine,
+}
+
+impl Sha256Context for Armv8Sha2Context {
+    fn new() -> Self {
+        Self {
+            engine: Engine::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.engine.update(bytes, compress);
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        self.engine.finalize(compress)
+    }
+}
+
+impl Sha256 for Armv8Sha2Impl {
+    type Context = Armv8Sha2Context;
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        self.hash_fixed(input).to_vec()
+    }
+
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        let mut ctxt = Armv8Sha2Context::new();
+        ctxt.update(input);
+        ctxt.finalize()
+    }
+
+    fn hash_64(&self, input: &[u8; 64]) -> [u8; HASH_LEN] {
+        let mut state = SHA256_IV;
+        compress(&mut state, &[*input, PADDING_BLOCK]);
+        state_to_digest(state)
+    }
+}
+
+/// Apply the compression function to `state` for each block in `blocks`, using the SHA2
+/// instructions where available.
+pub(crate) fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    if have_sha_extensions() {
+        // Safety: SHA2 availability was checked above.
+        unsafe { compress_sha2(state, blocks) }
+    } else {
+        compress_portable(state, blocks)
+    }
+}
+
+/// Each `vsha256hq_u32`/`vsha256h2q_u32` pair performs four rounds, and each
+/// `vsha256su0q_u32`/`vsha256su1q_u32` pair extends the message schedule by four words.
+#[target_feature(enable = "sha2")]
+unsafe fn compress_sha2(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    let mut abcd = vld1q_u32(state[..4].as_ptr());
+    let mut efgh = vld1q_u32(state[4..].as_ptr());
+
+    for block in blocks {
+        let (abcd_orig, efgh_orig) = (abcd, efgh);
+
+        // The message words are big-endian.
+        let mut w = [0, 16, 32, 48]
+            .map(|i| vreinterpretq_u32_u8(vrev32q_u8(vld1q_u8(block[i..].as_ptr()))));
+
+        for i in 0..16 {
+            if i >= 4 {
+                w[i % 4] = vsha256su1q_u32(
+                    vsha256su0q_u32(w[i % 4], w[(i + 1) % 4]),
+                    w[(i + 2) % 4],
+                    w[(i + 3) % 4],
+                );
+            }
+            let wk = vaddq_u32(w[i % 4], vld1q_u32(K[4 * i..].as_ptr()));
+            let abcd_prev = abcd;
+            abcd = vsha256hq_u32(abcd_prev, efgh, wk);
+            efgh = vsha256h2q_u32(efgh, abcd_prev, wk);
+        }
+
+        abcd = vaddq_u32(abcd, abcd_orig);
+        efgh = vaddq_u32(efgh, efgh_orig);
+    }
+
+    vst1q_u32(state[..4].as_mut_ptr(), abcd);
+    vst1q_u32(state[4..].as_mut_ptr(), efgh);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PortableImpl;
+
+    #[test]
+    fn matches_portable() {
+        let input: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 300] {
+            let input = &input[..len];
+            assert_eq!(
+                Armv8Sha2Impl.hash_fixed(input),
+                PortableImpl.hash_fixed(input),
+                "{len}"
+            );
+        }
+
+        let pair = [9; 64];
+        assert_eq!(Armv8Sha2Impl.hash_64(&pair), PortableImpl.hash_64(&pair));
+    }
+}
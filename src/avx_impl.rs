@@ -0,0 +1,514 @@
+// Multi-buffer SHA256 is only implemented for x86_64, where AVX2 and AVX-512 are available.
+#![cfg(target_arch = "x86_64")]
+
+use crate::compress::K;
+#[cfg(feature = "avx512")]
+use crate::have_avx512;
+use crate::{have_avx2, Sha256, HASH_LEN, SHA256_IV as IV};
+use alloc::vec::Vec;
+use core::arch::x86_64::*;
+
+// Single messages and leftover pairs are hashed by `ring`, or by `sha2` without the `ring`
+// feature.
+#[cfg(not(feature = "ring"))]
+use crate::sha2_impl::Sha2CrateImpl as ScalarImpl;
+#[cfg(feature = "ring")]
+use crate::RingImpl as ScalarImpl;
+
+/// Message schedule of the padding block that follows every 64-byte message, with the round
+/// constants already added.
+///
+/// The padding block is identical for all 64-byte messages, so its schedule only needs to be
+/// computed once.
+const PADDING_SCHEDULE: [u32; 64] = padding_schedule();
+
+const fn padding_schedule() -> [u32; 64] {
+    let mut w = [0u32; 64];
+    w[0] = 0x8000_0000;
+    w[15] = 512;
+
+    let mut i = 16;
+    while i < 64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 64 {
+        w[i] = w[i].wrapping_add(K[i]);
+        i += 1;
+    }
+
+    w
+}
+
+/// Multi-buffer implementation of SHA256 using AVX2, hashing 8 messages at once.
+///
+/// Only `hash32_concat_batch` is vectorised, everything else is delegated to `ring`
+/// (or `sha2` without the `ring` feature).
+pub struct Avx2Impl;
+
+/// Multi-buffer implementation of SHA256 using AVX-512, hashing 16 messages at once.
+///
+/// Only `hash32_concat_batch` is vectorised, everything else is delegated to `ring`
+/// (or `sha2` without the `ring` feature).
+///
+/// The AVX-512 intrinsics require Rust 1.89, so they're only compiled with the `avx512` feature.
+/// Without it, batches are hashed by `Avx2Impl`.
+pub struct Avx512Impl;
+
+impl Sha256 for Avx2Impl {
+    type Context = <ScalarImpl as Sha256>::Context;
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        ScalarImpl.hash(input)
+    }
+
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        ScalarImpl.hash_fixed(input)
+    }
+
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        ScalarImpl.hash_into(input, out)
+    }
+
+    fn hash32_concat_batch(&self, pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+        assert_eq!(
+            pairs.len(),
+            output.len(),
+            "input and output batches must be the same length"
+        );
+
+        if !have_avx2() {
+            return ScalarImpl.hash32_concat_batch(pairs, output);
+        }
+
+        let mut pair_chunks = pairs.chunks_exact(8);
+        let mut output_chunks = output.chunks_exact_mut(8);
+        for (pairs, output) in (&mut pair_chunks).zip(&mut output_chunks) {
+            // Safety: AVX2 availability was checked above.
+            unsafe { hash_64_x8(pairs.try_into().unwrap(), output.try_into().unwrap()) }
+        }
+        ScalarImpl.hash32_concat_batch(pair_chunks.remainder(), output_chunks.into_remainder());
+    }
+}
+
+impl Sha256 for Avx512Impl {
+    type Context = <ScalarImpl as Sha256>::Context;
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        ScalarImpl.hash(input)
+    }
+
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        ScalarImpl.hash_fixed(input)
+    }
+
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        ScalarImpl.hash_into(input, out)
+    }
+
+    fn hash32_concat_batch(&self, pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+        assert_eq!(
+            pairs.len(),
+            output.len(),
+            "input and output batches must be the same length"
+        );
+
+        #[cfg(feature = "avx512")]
+        if have_avx512() {
+            let mut pair_chunks = pairs.chunks_exact(16);
+            let mut output_chunks = output.chunks_exact_mut(16);
+            for (pairs, output) in (&mut pair_chunks).zip(&mut output_chunks) {
+                // Safety: AVX-512 availability was checked above.
+                unsafe { hash_64_x16(pairs.try_into().unwrap(), output.try_into().unwrap()) }
+            }
+            return Avx2Impl
+                .hash32_concat_batch(pair_chunks.remainder(), output_chunks.into_remainder());
+        }
+
+        Avx2Impl.hash32_concat_batch(pairs, output);
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn hash_64_x8(pairs: &[[u8; 64]; 8], output: &mut [[u8; HASH_LEN]; 8]) {
+    let mut state = IV.map(|word| __m256i::splat(word));
+    compress(&mut state, &__m256i::load_blocks(pairs));
+    compress_padding(&mut state);
+    __m256i::store_digests(&state, output);
+}
+
+#[cfg(feature = "avx512")]
+#[allow(clippy::incompatible_msrv)]
+#[target_feature(enable = "avx2,avx512f")]
+unsafe fn hash_64_x16(pairs: &[[u8; 64]; 16], output: &mut [[u8; HASH_LEN]; 16]) {
+    let mut state = IV.map(|word| __m512i::splat(word));
+    compress(&mut state, &__m512i::load_blocks(pairs));
+    compress_padding(&mut state);
+    __m512i::store_digests(&state, output);
+}
+
+/// Operations on a vector of independent 32-bit SHA256 words, one per message.
+///
+/// All methods are `unsafe` because they require the corresponding CPU feature, and must be
+/// inlined into a function compiled with that feature enabled.
+trait Lanes: Copy {
+    /// Messages hashed in parallel.
+    type Blocks;
+    /// Digests produced in parallel.
+    type Digests;
+
+    unsafe fn splat(word: u32) -> Self;
+    unsafe fn add(self, other: Self) -> Self;
+    unsafe fn and(self, other: Self) -> Self;
+    unsafe fn or(self, other: Self) -> Self;
+    unsafe fn xor(self, other: Self) -> Self;
+    /// Computes `!self & other`.
+    unsafe fn andnot(self, other: Self) -> Self;
+
+    unsafe fn big_sigma0(self) -> Self;
+    unsafe fn big_sigma1(self) -> Self;
+    unsafe fn small_sigma0(self) -> Self;
+    unsafe fn small_sigma1(self) -> Self;
+
+    /// Load the big-endian message words, transposed so that lane `i` holds message `i`.
+    unsafe fn load_blocks(blocks: &Self::Blocks) -> [Self; 16];
+    /// Store the state words as big-endian digests, one per lane.
+    unsafe fn store_digests(state: &[Self; 8], digests: &mut Self::Digests);
+}
+
+#[inline(always)]
+unsafe fn round<V: Lanes>(state: &mut [V; 8], k_plus_w: V) {
+    let [a, b, c, d, e, f, g, h] = *state;
+
+    let ch = e.and(f).xor(e.andnot(g));
+    let maj = a.and(b).or(c.and(a.or(b)));
+    let t1 = h.add(e.big_sigma1()).add(ch).add(k_plus_w);
+    let t2 = a.big_sigma0().add(maj);
+
+    *state = [t1.add(t2), a, b, c, d.add(t1), e, f, g];
+}
+
+#[inline(always)]
+unsafe fn compress<V: Lanes>(state: &mut [V; 8], block: &[V; 16]) {
+    let mut w = [V::splat(0); 64];
+    w[..16].copy_from_slice(block);
+    for i in 16..64 {
+        w[i] = w[i - 16]
+            .add(w[i - 15].small_sigma0())
+            .add(w[i - 7])
+            .add(w[i - 2].small_sigma1());
+    }
+
+    let mut working = *state;
+    for i in 0..64 {
+        round(&mut working, V::splat(K[i]).add(w[i]));
+    }
+    for (word, new) in state.iter_mut().zip(working) {
+        *word = word.add(new);
+    }
+}
+
+#[inline(always)]
+unsafe fn compress_padding<V: Lanes>(state: &mut [V; 8]) {
+    let mut working = *state;
+    for k_plus_w in PADDING_SCHEDULE {
+        round(&mut working, V::splat(k_plus_w));
+    }
+    for (word, new) in state.iter_mut().zip(working) {
+        *word = word.add(new);
+    }
+}
+
+/// Transpose an 8x8 matrix of 32-bit words stored as 8 rows.
+#[inline(always)]
+unsafe fn transpose_8x8(rows: [__m256i; 8]) -> [__m256i; 8] {
+    let t0 = _mm256_unpacklo_epi32(rows[0], rows[1]);
+    let t1 = _mm256_unpackhi_epi32(rows[0], rows[1]);
+    let t2 = _mm256_unpacklo_epi32(rows[2], rows[3]);
+    let t3 = _mm256_unpackhi_epi32(rows[2], rows[3]);
+    let t4 = _mm256_unpacklo_epi32(rows[4], rows[5]);
+    let t5 = _mm256_unpackhi_epi32(rows[4], rows[5]);
+    let t6 = _mm256_unpacklo_epi32(rows[6], rows[7]);
+    let t7 = _mm256_unpackhi_epi32(rows[6], rows[7]);
+
+    let u0 = _mm256_unpacklo_epi64(t0, t2);
+    let u1 = _mm256_unpackhi_epi64(t0, t2);
+    let u2 = _mm256_unpacklo_epi64(t1, t3);
+    let u3 = _mm256_unpackhi_epi64(t1, t3);
+    let u4 = _mm256_unpacklo_epi64(t4, t6);
+    let u5 = _mm256_unpackhi_epi64(t4, t6);
+    let u6 = _mm256_unpacklo_epi64(t5, t7);
+    let u7 = _mm256_unpackhi_epi64(t5, t7);
+
+    [
+        _mm256_permute2x128_si256::<0x20>(u0, u4),
+        _mm256_permute2x128_si256::<0x20>(u1, u5),
+        _mm256_permute2x128_si256::<0x20>(u2, u6),
+        _mm256_permute2x128_si256::<0x20>(u3, u7),
+        _mm256_permute2x128_si256::<0x31>(u0, u4),
+        _mm256_permute2x128_si256::<0x31>(u1, u5),
+        _mm256_permute2x128_si256::<0x31>(u2, u6),
+        _mm256_permute2x128_si256::<0x31>(u3, u7),
+    ]
+}
+
+/// Reverse the bytes of each 32-bit word.
+#[inline(always)]
+unsafe fn byte_swap_256(x: __m256i) -> __m256i {
+    let mask = _mm256_setr_epi8(
+        3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8, 15, 14, 13, 12, 3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8,
+        15, 14, 13, 12,
+    );
+    _mm256_shuffle_epi8(x, mask)
+}
+
+/// Load 8 consecutive big-endian words from each of 8 messages, starting at byte `offset`.
+#[inline(always)]
+unsafe fn load_words_x8(blocks: &[[u8; 64]], offset: usize) -> [__m256i; 8] {
+    let mut rows = [_mm256_setzero_si256(); 8];
+    for (row, block) in rows.iter_mut().zip(blocks) {
+        let words = _mm256_loadu_si256(block[offset..offset + 32].as_ptr().cast());
+        *row = byte_swap_256(words);
+    }
+    transpose_8x8(rows)
+}
+
+/// Store 8 state words for 8 messages as big-endian digests.
+#[inline(always)]
+unsafe fn store_digests_x8(state: [__m256i; 8], digests: &mut [[u8; HASH_LEN]]) {
+    for (digest, row) in digests.iter_mut().zip(transpose_8x8(state)) {
+        _mm256_storeu_si256(digest.as_mut_ptr().cast(), byte_swap_256(row));
+    }
+}
+
+impl Lanes for __m256i {
+    type Blocks = [[u8; 64]; 8];
+    type Digests = [[u8; HASH_LEN]; 8];
+
+    #[inline(always)]
+    unsafe fn splat(word: u32) -> Self {
+        _mm256_set1_epi32(word as i32)
+    }
+
+    #[inline(always)]
+    unsafe fn add(self, other: Self) -> Self {
+        _mm256_add_epi32(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn and(self, other: Self) -> Self {
+        _mm256_and_si256(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn or(self, other: Self) -> Self {
+        _mm256_or_si256(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn xor(self, other: Self) -> Self {
+        _mm256_xor_si256(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn andnot(self, other: Self) -> Self {
+        _mm256_andnot_si256(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn big_sigma0(self) -> Self {
+        // rotr(2) ^ rotr(13) ^ rotr(22)
+        let right = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_srli_epi32::<2>(self), _mm256_srli_epi32::<13>(self)),
+            _mm256_srli_epi32::<22>(self),
+        );
+        let left = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_slli_epi32::<30>(self), _mm256_slli_epi32::<19>(self)),
+            _mm256_slli_epi32::<10>(self),
+        );
+        _mm256_xor_si256(right, left)
+    }
+
+    #[inline(always)]
+    unsafe fn big_sigma1(self) -> Self {
+        // rotr(6) ^ rotr(11) ^ rotr(25)
+        let right = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_srli_epi32::<6>(self), _mm256_srli_epi32::<11>(self)),
+            _mm256_srli_epi32::<25>(self),
+        );
+        let left = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_slli_epi32::<26>(self), _mm256_slli_epi32::<21>(self)),
+            _mm256_slli_epi32::<7>(self),
+        );
+        _mm256_xor_si256(right, left)
+    }
+
+    #[inline(always)]
+    unsafe fn small_sigma0(self) -> Self {
+        // rotr(7) ^ rotr(18) ^ shr(3)
+        let right = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_srli_epi32::<7>(self), _mm256_srli_epi32::<18>(self)),
+            _mm256_srli_epi32::<3>(self),
+        );
+        let left = _mm256_xor_si256(_mm256_slli_epi32::<25>(self), _mm256_slli_epi32::<14>(self));
+        _mm256_xor_si256(right, left)
+    }
+
+    #[inline(always)]
+    unsafe fn small_sigma1(self) -> Self {
+        // rotr(17) ^ rotr(19) ^ shr(10)
+        let right = _mm256_xor_si256(
+            _mm256_xor_si256(_mm256_srli_epi32::<17>(self), _mm256_srli_epi32::<19>(self)),
+            _mm256_srli_epi32::<10>(self),
+        );
+        let left = _mm256_xor_si256(_mm256_slli_epi32::<15>(self), _mm256_slli_epi32::<13>(self));
+        _mm256_xor_si256(right, left)
+    }
+
+    #[inline(always)]
+    unsafe fn load_blocks(blocks: &Self::Blocks) -> [Self; 16] {
+        let mut words = [_mm256_setzero_si256(); 16];
+        words[..8].copy_from_slice(&load_words_x8(blocks, 0));
+        words[8..].copy_from_slice(&load_words_x8(blocks, 32));
+        words
+    }
+
+    #[inline(always)]
+    unsafe fn store_digests(state: &[Self; 8], digests: &mut Self::Digests) {
+        store_digests_x8(*state, digests);
+    }
+}
+
+#[cfg(feature = "avx512")]
+#[allow(clippy::incompatible_msrv)]
+impl Lanes for __m512i {
+    type Blocks = [[u8; 64]; 16];
+    type Digests = [[u8; HASH_LEN]; 16];
+
+    #[inline(always)]
+    unsafe fn splat(word: u32) -> Self {
+        _mm512_set1_epi32(word as i32)
+    }
+
+    #[inline(always)]
+    unsafe fn add(self, other: Self) -> Self {
+        _mm512_add_epi32(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn and(self, other: Self) -> Self {
+        _mm512_and_si512(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn or(self, other: Self) -> Self {
+        _mm512_or_si512(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn xor(self, other: Self) -> Self {
+        _mm512_xor_si512(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn andnot(self, other: Self) -> Self {
+        _mm512_andnot_si512(self, other)
+    }
+
+    #[inline(always)]
+    unsafe fn big_sigma0(self) -> Self {
+        _mm512_xor_si512(
+            _mm512_xor_si512(_mm512_ror_epi32::<2>(self), _mm512_ror_epi32::<13>(self)),
+            _mm512_ror_epi32::<22>(self),
+        )
+    }
+
+    #[inline(always)]
+    unsafe fn big_sigma1(self) -> Self {
+        _mm512_xor_si512(
+            _mm512_xor_si512(_mm512_ror_epi32::<6>(self), _mm512_ror_epi32::<11>(self)),
+            _mm512_ror_epi32::<25>(self),
+        )
+    }
+
+    #[inline(always)]
+    unsafe fn small_sigma0(self) -> Self {
+        _mm512_xor_si512(
+            _mm512_xor_si512(_mm512_ror_epi32::<7>(self), _mm512_ror_epi32::<18>(self)),
+            _mm512_srli_epi32::<3>(self),
+        )
+    }
+
+    #[inline(always)]
+    unsafe fn small_sigma1(self) -> Self {
+        _mm512_xor_si512(
+            _mm512_xor_si512(_mm512_ror_epi32::<17>(self), _mm512_ror_epi32::<19>(self)),
+            _mm512_srli_epi32::<10>(self),
+        )
+    }
+
+    #[inline(always)]
+    unsafe fn load_blocks(blocks: &Self::Blocks) -> [Self; 16] {
+        let (low_blocks, high_blocks) = blocks.split_at(8);
+        let mut words = [_mm512_setzero_si512(); 16];
+        for (offset, words) in [0, 32].into_iter().zip(words.chunks_exact_mut(8)) {
+            let low = load_words_x8(low_blocks, offset);
+            let high = load_words_x8(high_blocks, offset);
+            for ((word, low), high) in words.iter_mut().zip(low).zip(high) {
+                *word = _mm512_inserti64x4::<1>(_mm512_castsi256_si512(low), high);
+            }
+        }
+        words
+    }
+
+    #[inline(always)]
+    unsafe fn store_digests(state: &[Self; 8], digests: &mut Self::Digests) {
+        let (low_digests, high_digests) = digests.split_at_mut(8);
+        store_digests_x8(state.map(|word| _mm512_castsi512_si256(word)), low_digests);
+        store_digests_x8(
+            state.map(|word| _mm512_extracti64x4_epi64::<1>(word)),
+            high_digests,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PortableImpl;
+
+    fn test_pairs(count: usize) -> Vec<[u8; 64]> {
+        (0..count)
+            .map(|i| std::array::from_fn(|j| (i * 64 + j).wrapping_mul(31) as u8))
+            .collect()
+    }
+
+    fn check_against_portable(implementation: impl Sha256) {
+        for count in [0, 1, 7, 8, 9, 15, 16, 17, 33, 100] {
+            let pairs = test_pairs(count);
+            let mut expected = vec![[0; HASH_LEN]; count];
+            let mut output = vec![[0; HASH_LEN]; count];
+
+            PortableImpl.hash32_concat_batch(&pairs, &mut expected);
+            implementation.hash32_concat_batch(&pairs, &mut output);
+            assert_eq!(output, expected, "mismatch for batch of {count}");
+        }
+    }
+
+    #[test]
+    fn avx2_matches_portable() {
+        check_against_portable(Avx2Impl);
+    }
+
+    #[test]
+    fn avx512_matches_portable() {
+        check_against_portable(Avx512Impl);
+    }
+}
@@ -0,0 +1,324 @@
+//! Explicit selection of the implementation used by `DynamicImpl`.
+use crate::DynamicImpl;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Environment variable read on first use to pin the backend, e.g.
+/// `ETHEREUM_HASHING_BACKEND=ring`. The value `auto` (or an empty value) keeps automatic
+/// selection.
+///
+/// The environment is only read with the `std` feature.
+pub const BACKEND_ENV_VAR: &str = "ETHEREUM_HASHING_BACKEND";
+
+/// A SHA256 implementation that `DynamicImpl::best()` can be pinned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// SHA intrinsics when the CPU supports them, through the `sha2` crate on x86_64 and
+    /// `Armv8Sha2Impl` on aarch64.
+    Sha2,
+    /// AVX-512 multi-buffer hashing of batches, with `ring` (or `sha2` without the `ring`
+    /// feature) for single messages.
+    Avx512,
+    /// AVX2 multi-buffer hashing of batches, with `ring` (or `sha2` without the `ring` feature)
+    /// for single messages.
+    Avx2,
+    /// The `ring` crate.
+    Ring,
+    /// The portable pure-Rust implementation, available everywhere.
+    Portable,
+}
+
+/// Error returned when a backend can't be selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// The backend isn't compiled in for this target, or the CPU lacks the features it needs.
+    Unavailable(Backend),
+    /// The name doesn't match any backend.
+    UnknownBackend(String),
+    /// The backend failed the known-answer tests run by `self_test`.
+    Disabled(Backend),
+}
+
+/// Selection state: `UNINIT` until the environment has been read, then `AUTO` or
+/// `FORCED + i` for `Backend::ALL[i]`.
+static SELECTION: AtomicU8 = AtomicU8::new(UNINIT);
+
+const UNINIT: u8 = 0;
+const AUTO: u8 = 1;
+const FORCED: u8 = 2;
+
+/// Bit `i` is set if `Backend::ALL[i]` has been disabled by `self_test`.
+static DISABLED: AtomicU8 = AtomicU8::new(0);
+
+impl Backend {
+    /// Every backend, in declaration order.
+    pub const ALL: [Self; 5] = [
+        Self::Sha2,
+        Self::Avx512,
+        Self::Avx2,
+        Self::Ring,
+        Self::Portable,
+    ];
+
+    /// The name accepted by `FromStr` and `BACKEND_ENV_VAR`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Sha2 => "sha2",
+            Self::Avx512 => "avx512",
+            Self::Avx2 => "avx2",
+            Self::Ring => "ring",
+            Self::Portable => "portable",
+        }
+    }
+
+    /// Whether the backend can be used on the currently executing CPU, and hasn't been disabled
+    /// by `self_test`.
+    ///
+    /// `Sha2` is available wherever it is compiled in (x86_64 and aarch64), even without SHA
+    /// intrinsics, in which case it falls back to a software implementation.
+    pub fn is_available(self) -> bool {
+        self.is_supported() && !self.is_disabled()
+    }
+
+    /// Whether the backend failed `self_test` and is no longer used.
+    pub fn is_disabled(self) -> bool {
+        DISABLED.load(Ordering::Relaxed) & self.bit() != 0
+    }
+
+    /// Use this backend for all subsequent hashing, overriding automatic selection and
+    /// `BACKEND_ENV_VAR`.
+    ///
+    /// Contexts created before the call keep using the backend they were created with.
+    pub fn force(self) -> Result<(), BackendError> {
+        initialize();
+        if !self.is_supported() {
+            return Err(BackendError::Unavailable(self));
+        }
+        if self.is_disabled() {
+            return Err(BackendError::Disabled(self));
+        }
+        SELECTION.store(FORCED + self.position(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Undo `force` and `BACKEND_ENV_VAR`, returning to automatic selection.
+    pub fn clear() {
+        initialize();
+        SELECTION.store(AUTO, Ordering::Relaxed);
+    }
+
+    /// The backend currently used by `DynamicImpl::best()`.
+    pub fn current() -> Self {
+        match DynamicImpl::best() {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            DynamicImpl::Sha2 => Self::Sha2,
+            #[cfg(target_arch = "x86_64")]
+            DynamicImpl::Avx512 => Self::Avx512,
+            #[cfg(target_arch = "x86_64")]
+            DynamicImpl::Avx2 => Self::Avx2,
+            #[cfg(feature = "ring")]
+            DynamicImpl::Ring => Self::Ring,
+            DynamicImpl::Portable => Self::Portable,
+        }
+    }
+
+    /// The backend named by `BACKEND_ENV_VAR`, or `None` if it is unset or `auto`.
+    #[cfg(feature = "std")]
+    pub fn from_env() -> Result<Option<Self>, BackendError> {
+        match std::env::var(BACKEND_ENV_VAR) {
+            Ok(value) => parse_selection(&value),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Apply `BACKEND_ENV_VAR`, returning an error if it names an unknown or unavailable backend.
+    ///
+    /// The variable is also read automatically the first time a backend is chosen, but errors are
+    /// ignored there, so call this at startup to report a misconfiguration.
+    #[cfg(feature = "std")]
+    pub fn init_from_env() -> Result<(), BackendError> {
+        match Self::from_env()? {
+            Some(backend) => backend.force(),
+            None => {
+                Self::clear();
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the backend is compiled in and the CPU has the features it needs.
+    pub(crate) fn is_supported(self) -> bool {
+        self.dynamic_impl().is_some()
+    }
+
+    /// Stop using the backend, for both automatic selection and `force`.
+    pub(crate) fn disable(self) {
+        DISABLED.fetch_or(self.bit(), Ordering::Relaxed);
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self.position()
+    }
+
+    /// Index of the backend in `ALL`, which follows the declaration order.
+    const fn position(self) -> u8 {
+        self as u8
+    }
+
+    pub(crate) fn dynamic_impl(self) -> Option<DynamicImpl> {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Some(DynamicImpl::Sha2),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 if crate::have_avx512() => Some(DynamicImpl::Avx512),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 if crate::have_avx2() => Some(DynamicImpl::Avx2),
+            #[cfg(feature = "ring")]
+            Self::Ring => Some(DynamicImpl::Ring),
+            Self::Portable => Some(DynamicImpl::Portable),
+            _ => None,
+        }
+    }
+}
+
+/// The implementation pinned by `Backend::force` or `BACKEND_ENV_VAR`, if any.
+#[inline(always)]
+pub(crate) fn forced() -> Option<DynamicImpl> {
+    let selection = match SELECTION.load(Ordering::Relaxed) {
+        UNINIT => init_selection(),
+        selection => selection,
+    };
+    let backend = Backend::ALL.get(selection.checked_sub(FORCED)? as usize)?;
+    if backend.is_disabled() {
+        return None;
+    }
+    backend.dynamic_impl()
+}
+
+/// Read `BACKEND_ENV_VAR` (with `std`) and run the self-test (with `lazy_self_test`) if this is
+/// the first use.
+pub(crate) fn initialize() {
+    if SELECTION.load(Ordering::Relaxed) == UNINIT {
+        init_selection();
+    }
+}
+
+#[cold]
+fn init_selection() -> u8 {
+    #[cfg(feature = "lazy_self_test")]
+    crate::self_test::lazy_self_test();
+
+    #[cfg(feature = "std")]
+    let selection = match Backend::from_env() {
+        Ok(Some(backend)) if backend.is_available() => FORCED + backend.position(),
+        _ => AUTO,
+    };
+    #[cfg(not(feature = "std"))]
+    let selection = AUTO;
+
+    // Don't overwrite a selection made by another thread in the meantime.
+    match SELECTION.compare_exchange(UNINIT, selection, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => selection,
+        Err(current) => current,
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+fn parse_selection(value: &str) -> Result<Option<Backend>, BackendError> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("auto") {
+        Ok(None)
+    } else {
+        value.parse().map(Some)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = BackendError;
+
+    /// Parse a backend name, ignoring ASCII case.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| BackendError::UnknownBackend(name.to_owned()))
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unavailable(backend) => write!(f, "hashing backend {backend} is unavailable"),
+            Self::UnknownBackend(name) => write!(f, "unknown hashing backend {name:?}"),
+            Self::Disabled(backend) => {
+                write!(f, "hashing backend {backend} failed its self-test")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BackendError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::compress_block;
+    use crate::{compress256, hash, PortableImpl, Sha256, SHA256_IV};
+
+    #[test]
+    fn parse() {
+        for backend in Backend::ALL {
+            assert_eq!(backend.to_string().parse(), Ok(backend));
+        }
+        assert_eq!("RING".parse(), Ok(Backend::Ring));
+        assert_eq!(
+            "md5".parse::<Backend>(),
+            Err(BackendError::UnknownBackend("md5".into()))
+        );
+        assert_eq!(
+            BackendError::UnknownBackend("md5".into()).to_string(),
+            "unknown hashing backend \"md5\""
+        );
+        assert_eq!(parse_selection(""), Ok(None));
+        assert_eq!(parse_selection(" Auto "), Ok(None));
+        assert_eq!(parse_selection("sha2"), Ok(Some(Backend::Sha2)));
+    }
+
+    // Everything that touches the global selection lives in this one test, since tests run in
+    // parallel.
+    #[test]
+    fn force() {
+        let input = b"ethereum hashing";
+        let expected = PortableImpl.hash(input);
+        let blocks = [[0x5a; 64], [0xa5; 64]];
+        let expected_state = blocks.iter().fold(SHA256_IV, compress_block);
+
+        assert!(Backend::Portable.is_available());
+        for backend in Backend::ALL {
+            if backend.is_available() {
+                assert_eq!(backend.force(), Ok(()));
+                assert_eq!(Backend::current(), backend);
+                assert_eq!(hash(input), expected, "{backend}");
+                let mut state = SHA256_IV;
+                compress256(&mut state, &blocks);
+                assert_eq!(state, expected_state, "{backend}");
+            } else {
+                assert_eq!(backend.force(), Err(BackendError::Unavailable(backend)));
+            }
+        }
+
+        Backend::clear();
+        assert_eq!(DynamicImpl::best(), DynamicImpl::detect());
+    }
+}
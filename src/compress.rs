@@ -0,0 +1,166 @@
+//! The raw SHA256 compression function.
+#[cfg(target_arch = "aarch64")]
+use crate::armv8_impl::compress as compress_sha2;
+#[cfg(target_arch = "x86_64")]
+use crate::sha2_impl::compress as compress_sha2;
+
+/// SHA256 initial hash value, the state before any block has been compressed.
+pub const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA256 round constants.
+pub(crate) const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The padding block that follows every 64-byte message: a single set bit, zeros, and the message
+/// length of 512 bits.
+pub(crate) const PADDING_BLOCK: [u8; 64] = {
+    let mut block = [0; 64];
+    block[0] = 0x80;
+    block[62] = 0x02;
+    block
+};
+
+/// Apply the SHA256 compression function to `state` for each block in `blocks`, in order.
+///
+/// No padding is added, so hashing a message this way requires appending the padding and length
+/// as specified by FIPS 180-4. The digest is the big-endian encoding of the final state.
+///
+/// The SHA2 compressor, through the `sha2` crate on x86_64 and `Armv8Sha2Impl` on aarch64, is
+/// used whatever the selected backend unless it failed its self-test, since `ring` and the AVX
+/// backends have no equivalent. It falls back to software on CPUs without the SHA extensions.
+/// Other targets use the portable implementation.
+pub fn compress256(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    {
+        crate::backend::initialize();
+        if !crate::Backend::Sha2.is_disabled() {
+            return compress_sha2(state, blocks);
+        }
+    }
+
+    compress_portable(state, blocks)
+}
+
+/// Portable compression of each block in `blocks`.
+pub(crate) fn compress_portable(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    for block in blocks {
+        *state = compress_block(*state, block);
+    }
+}
+
+/// Portable compression of a single block.
+pub(crate) const fn compress_block(state: [u32; 8], block: &[u8; 64]) -> [u32; 8] {
+    let mut w = [0u32; 64];
+    let mut i = 0;
+    while i < 16 {
+        w[i] = u32::from_be_bytes([
+            block[4 * i],
+            block[4 * i + 1],
+            block[4 * i + 2],
+            block[4 * i + 3],
+        ]);
+        i += 1;
+    }
+    while i < 64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+        i += 1;
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+    let mut i = 0;
+    while i < 64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+        i += 1;
+    }
+
+    [
+        state[0].wrapping_add(a),
+        state[1].wrapping_add(b),
+        state[2].wrapping_add(c),
+        state[3].wrapping_add(d),
+        state[4].wrapping_add(e),
+        state[5].wrapping_add(f),
+        state[6].wrapping_add(g),
+        state[7].wrapping_add(h),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_fixed;
+
+    fn digest(state: [u32; 8]) -> [u8; 32] {
+        let mut output = [0; 32];
+        for (bytes, word) in output.chunks_exact_mut(4).zip(state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        output
+    }
+
+    #[test]
+    fn padded_message() {
+        // "abc", padded to a single block.
+        let mut block = [0; 64];
+        block[..3].copy_from_slice(b"abc");
+        block[3] = 0x80;
+        block[63] = 24;
+
+        let mut state = SHA256_IV;
+        compress256(&mut state, &[block]);
+        assert_eq!(digest(state), hash_fixed(b"abc"));
+        assert_eq!(
+            digest(compress_block(SHA256_IV, &block)),
+            hash_fixed(b"abc")
+        );
+    }
+
+    #[test]
+    fn portable_matches_compress256() {
+        let blocks: Vec<[u8; 64]> = (0..5u8)
+            .map(|i| std::array::from_fn(|j| i.wrapping_mul(97).wrapping_add(j as u8)))
+            .collect();
+
+        let mut state = SHA256_IV;
+        compress256(&mut state, &blocks);
+        let portable = blocks.iter().fold(SHA256_IV, compress_block);
+        assert_eq!(state, portable);
+
+        let mut empty = SHA256_IV;
+        compress256(&mut empty, &[]);
+        assert_eq!(empty, SHA256_IV);
+    }
+}
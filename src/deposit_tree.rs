@@ -0,0 +1,555 @@
+//! Incremental Merkle tree of deposits with finalization and snapshots, as defined by EIP-4881.
+//!
+//! The tree mirrors the one kept by the deposit contract. Once deposits are finalized, the
+//! subtrees containing only finalized deposits are collapsed into their roots. What remains of the
+//! finalized part of the tree is its left-branch frontier, which is all that needs to be stored in
+//! a `DepositTreeSnapshot`.
+use crate::{hash32_concat, length_chunk, mix_in_length_u64, HASH_LEN, ZERO_HASHES};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Depth of the deposit contract's Merkle tree, excluding the length mix-in.
+pub const DEPOSIT_CONTRACT_TREE_DEPTH: usize = 32;
+
+/// Error returned by operations on a `DepositTree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositTreeError {
+    /// The tree already contains `2^DEPOSIT_CONTRACT_TREE_DEPTH` deposits.
+    TreeFull,
+    /// Tried to finalize more deposits than the tree contains, or fewer than are already
+    /// finalized.
+    InvalidFinalization { deposit_count: u64 },
+    /// Tried to create a proof for a finalized deposit, or one that doesn't exist.
+    InvalidProofIndex { index: u64 },
+    /// A snapshot was requested before any deposits were finalized.
+    NotFinalized,
+    /// The snapshot's finalized nodes are inconsistent with its deposit count or deposit root.
+    InvalidSnapshot,
+}
+
+impl fmt::Display for DepositTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TreeFull => f.write_str("deposit tree is full"),
+            Self::InvalidFinalization { deposit_count } => {
+                write!(f, "can't finalize the first {deposit_count} deposits")
+            }
+            Self::InvalidProofIndex { index } => {
+                write!(
+                    f,
+                    "no proof for deposit {index}, which is finalized or missing"
+                )
+            }
+            Self::NotFinalized => f.write_str("no deposits have been finalized"),
+            Self::InvalidSnapshot => f.write_str("inconsistent deposit tree snapshot"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DepositTreeError {}
+
+/// Snapshot of the finalized part of a `DepositTree`, from which the tree can be restored.
+///
+/// This matches the `DepositTreeSnapshot` container from EIP-4881.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositTreeSnapshot {
+    /// Roots of the finalized subtrees, from left to right.
+    pub finalized: Vec<[u8; HASH_LEN]>,
+    /// Root of the tree, including the length mix-in.
+    pub deposit_root: [u8; HASH_LEN],
+    /// Number of finalized deposits.
+    pub deposit_count: u64,
+    /// Hash of the execution block at which the deposits were finalized.
+    pub execution_block_hash: [u8; HASH_LEN],
+    /// Height of the execution block at which the deposits were finalized.
+    pub execution_block_height: u64,
+}
+
+impl DepositTreeSnapshot {
+    /// Compute the deposit root from the finalized nodes and deposit count.
+    ///
+    /// Returns `None` if the number of finalized nodes doesn't match the deposit count.
+    pub fn calculate_root(&self) -> Option<[u8; HASH_LEN]> {
+        if self.finalized.len() != self.deposit_count.count_ones() as usize
+            || self.deposit_count > 1 << DEPOSIT_CONTRACT_TREE_DEPTH
+        {
+            return None;
+        }
+
+        let mut size = self.deposit_count;
+        let mut finalized = self.finalized.iter().rev();
+        let mut root = ZERO_HASHES[0];
+        for zero_hash in &ZERO_HASHES[..DEPOSIT_CONTRACT_TREE_DEPTH] {
+            if size & 1 == 1 {
+                root = hash32_concat(finalized.next()?, &root);
+            } else {
+                root = hash32_concat(&root, zero_hash);
+            }
+            size >>= 1;
+        }
+
+        Some(mix_in_length_u64(&root, self.deposit_count))
+    }
+}
+
+/// Append-only Merkle tree of deposits, as defined by EIP-4881.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositTree {
+    tree: MerkleTree,
+    deposit_count: u64,
+    finalized_deposit_count: u64,
+    finalized_execution_block: Option<([u8; HASH_LEN], u64)>,
+}
+
+impl Default for DepositTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DepositTree {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self {
+            tree: MerkleTree::Zero(DEPOSIT_CONTRACT_TREE_DEPTH),
+            deposit_count: 0,
+            finalized_deposit_count: 0,
+            finalized_execution_block: None,
+        }
+    }
+
+    /// Restore a tree from a snapshot of its finalized part.
+    pub fn from_snapshot(snapshot: &DepositTreeSnapshot) -> Result<Self, DepositTreeError> {
+        if snapshot.calculate_root() != Some(snapshot.deposit_root) {
+            return Err(DepositTreeError::InvalidSnapshot);
+        }
+
+        Ok(Self {
+            tree: MerkleTree::from_snapshot_parts(
+                &snapshot.finalized,
+                snapshot.deposit_count,
+                DEPOSIT_CONTRACT_TREE_DEPTH,
+            ),
+            deposit_count: snapshot.deposit_count,
+            finalized_deposit_count: snapshot.deposit_count,
+            finalized_execution_block: Some((
+                snapshot.execution_block_hash,
+                snapshot.execution_block_height,
+            )),
+        })
+    }
+
+    /// Take a snapshot of the finalized part of the tree.
+    pub fn get_snapshot(&self) -> Result<DepositTreeSnapshot, DepositTreeError> {
+        let (execution_block_hash, execution_block_height) = self
+            .finalized_execution_block
+            .ok_or(DepositTreeError::NotFinalized)?;
+
+        let mut finalized = vec![];
+        let deposit_count = self.tree.get_finalized(&mut finalized);
+        let mut snapshot = DepositTreeSnapshot {
+            finalized,
+            deposit_root: [0; HASH_LEN],
+            deposit_count,
+            execution_block_hash,
+            execution_block_height,
+        };
+        snapshot.deposit_root = snapshot
+            .calculate_root()
+            .ok_or(DepositTreeError::InvalidSnapshot)?;
+
+        Ok(snapshot)
+    }
+
+    /// Root of the tree, including the length mix-in.
+    ///
+    /// This is the value returned by the deposit contract's `get_deposit_root`.
+    pub fn root(&self) -> [u8; HASH_LEN] {
+        mix_in_length_u64(&self.tree.root(), self.deposit_count)
+    }
+
+    /// Number of deposits in the tree.
+    pub fn deposit_count(&self) -> u64 {
+        self.deposit_count
+    }
+
+    /// Number of finalized deposits in the tree.
+    pub fn finalized_deposit_count(&self) -> u64 {
+        self.finalized_deposit_count
+    }
+
+    /// Append a deposit to the tree.
+    pub fn push_leaf(&mut self, leaf: [u8; HASH_LEN]) -> Result<(), DepositTreeError> {
+        self.tree.push_leaf(leaf, DEPOSIT_CONTRACT_TREE_DEPTH)?;
+        self.deposit_count += 1;
+        Ok(())
+    }
+
+    /// Finalize the first `deposit_count` deposits, as of the given execution block.
+    ///
+    /// Finalized deposits can no longer be proven, and are dropped from the tree.
+    pub fn finalize(
+        &mut self,
+        deposit_count: u64,
+        execution_block_hash: [u8; HASH_LEN],
+        execution_block_height: u64,
+    ) -> Result<(), DepositTreeError> {
+        if deposit_count > self.deposit_count || deposit_count < self.finalized_deposit_count {
+            return Err(DepositTreeError::InvalidFinalization { deposit_count });
+        }
+
+        self.tree
+            .finalize(deposit_count, DEPOSIT_CONTRACT_TREE_DEPTH);
+        self.finalized_deposit_count = deposit_count;
+        self.finalized_execution_block = Some((execution_block_hash, execution_block_height));
+        Ok(())
+    }
+
+    /// Create a proof that the deposit at `index` is included in the tree.
+    ///
+    /// Returns the deposit and its branch, which includes the length mix-in. The proof can be
+    /// checked with `is_valid_merkle_branch` using a depth of `DEPOSIT_CONTRACT_TREE_DEPTH + 1`.
+    pub fn get_proof(
+        &self,
+        index: u64,
+    ) -> Result<([u8; HASH_LEN], Vec<[u8; HASH_LEN]>), DepositTreeError> {
+        if index >= self.deposit_count || index < self.finalized_deposit_count {
+            return Err(DepositTreeError::InvalidProofIndex { index });
+        }
+
+        let (leaf, mut proof) = self
+            .tree
+            .generate_proof(index, DEPOSIT_CONTRACT_TREE_DEPTH)
+            .ok_or(DepositTreeError::InvalidProofIndex { index })?;
+        proof.push(length_chunk(self.deposit_count));
+
+        Ok((leaf, proof))
+    }
+}
+
+/// Node of the deposit tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MerkleTree {
+    /// Interior node with at least one non-finalized deposit, with its root cached.
+    Node {
+        root: [u8; HASH_LEN],
+        left: Box<MerkleTree>,
+        right: Box<MerkleTree>,
+    },
+    /// Non-finalized deposit.
+    Leaf([u8; HASH_LEN]),
+    /// Full subtree of finalized deposits, collapsed into its root.
+    Finalized {
+        deposit_count: u64,
+        root: [u8; HASH_LEN],
+    },
+    /// Empty subtree of the given depth.
+    Zero(usize),
+}
+
+impl MerkleTree {
+    fn node(left: MerkleTree, right: MerkleTree) -> Self {
+        Self::Node {
+            root: hash32_concat(&left.root(), &right.root()),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Create a tree of the given `depth` containing only `leaf`.
+    fn create(leaf: [u8; HASH_LEN], depth: usize) -> Self {
+        if depth == 0 {
+            Self::Leaf(leaf)
+        } else {
+            Self::node(Self::create(leaf, depth - 1), Self::Zero(depth - 1))
+        }
+    }
+
+    /// Rebuild the frontier of a tree of the given `depth` from its finalized subtree roots.
+    fn from_snapshot_parts(finalized: &[[u8; HASH_LEN]], deposit_count: u64, depth: usize) -> Self {
+        let Some((first, rest)) = finalized.split_first() else {
+            return Self::Zero(depth);
+        };
+        if deposit_count == 0 {
+            return Self::Zero(depth);
+        }
+        if deposit_count == 1 << depth {
+            return Self::Finalized {
+                deposit_count,
+                root: *first,
+            };
+        }
+
+        let node_size = 1 << (depth - 1);
+        if deposit_count <= node_size {
+            Self::node(
+                Self::from_snapshot_parts(finalized, deposit_count, depth - 1),
+                Self::Zero(depth - 1),
+            )
+        } else {
+            Self::node(
+                Self::Finalized {
+                    deposit_count: node_size,
+                    root: *first,
+                },
+                Self::from_snapshot_parts(rest, deposit_count - node_size, depth - 1),
+            )
+        }
+    }
+
+    fn root(&self) -> [u8; HASH_LEN] {
+        match self {
+            Self::Node { root, .. } | Self::Leaf(root) | Self::Finalized { root, .. } => *root,
+            Self::Zero(depth) => ZERO_HASHES[*depth],
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        match self {
+            Self::Node { right, .. } => right.is_full(),
+            Self::Leaf(_) | Self::Finalized { .. } => true,
+            Self::Zero(_) => false,
+        }
+    }
+
+    fn push_leaf(&mut self, leaf: [u8; HASH_LEN], depth: usize) -> Result<(), DepositTreeError> {
+        match self {
+            Self::Node { root, left, right } => {
+                if !left.is_full() {
+                    left.push_leaf(leaf, depth - 1)?;
+                } else {
+                    right.push_leaf(leaf, depth - 1)?;
+                }
+                *root = hash32_concat(&left.root(), &right.root());
+            }
+            Self::Leaf(_) | Self::Finalized { .. } => return Err(DepositTreeError::TreeFull),
+            Self::Zero(_) => *self = Self::create(leaf, depth),
+        }
+        Ok(())
+    }
+
+    /// Collapse all subtrees containing only the first `deposit_count` deposits.
+    fn finalize(&mut self, deposit_count: u64, depth: usize) {
+        if deposit_count == 0 {
+            return;
+        }
+        match self {
+            Self::Node { root, left, right } => {
+                if deposit_count >= 1 << depth {
+                    *self = Self::Finalized {
+                        deposit_count: 1 << depth,
+                        root: *root,
+                    };
+                    return;
+                }
+
+                let left_capacity = 1 << (depth - 1);
+                left.finalize(deposit_count, depth - 1);
+                if deposit_count > left_capacity {
+                    right.finalize(deposit_count - left_capacity, depth - 1);
+                }
+            }
+            Self::Leaf(root) => {
+                *self = Self::Finalized {
+                    deposit_count: 1,
+                    root: *root,
+                }
+            }
+            Self::Finalized { .. } | Self::Zero(_) => {}
+        }
+    }
+
+    /// Append the roots of the finalized subtrees to `result`, returning the number of finalized
+    /// deposits.
+    fn get_finalized(&self, result: &mut Vec<[u8; HASH_LEN]>) -> u64 {
+        match self {
+            Self::Node { left, right, .. } => {
+                left.get_finalized(result) + right.get_finalized(result)
+            }
+            Self::Finalized {
+                deposit_count,
+                root,
+            } => {
+                result.push(*root);
+                *deposit_count
+            }
+            Self::Leaf(_) | Self::Zero(_) => 0,
+        }
+    }
+
+    /// Return the leaf at `index` and its branch, or `None` if the leaf has been finalized.
+    fn generate_proof(
+        &self,
+        index: u64,
+        depth: usize,
+    ) -> Option<([u8; HASH_LEN], Vec<[u8; HASH_LEN]>)> {
+        let mut proof = Vec::with_capacity(depth);
+        let mut node = self;
+        for level in (0..depth).rev() {
+            let Self::Node { left, right, .. } = node else {
+                return None;
+            };
+            if (index >> level) & 1 == 1 {
+                proof.push(left.root());
+                node = right;
+            } else {
+                proof.push(right.root());
+                node = left;
+            }
+        }
+        proof.reverse();
+
+        match node {
+            Self::Leaf(leaf) => Some((*leaf, proof)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_fixed, is_valid_merkle_branch, merkleize};
+
+    fn deposits(count: u64) -> Vec<[u8; HASH_LEN]> {
+        (0..count).map(|i| hash_fixed(&i.to_le_bytes())).collect()
+    }
+
+    fn expected_root(deposits: &[[u8; HASH_LEN]]) -> [u8; HASH_LEN] {
+        let root = merkleize(deposits, Some(1 << DEPOSIT_CONTRACT_TREE_DEPTH));
+        mix_in_length_u64(&root, deposits.len() as u64)
+    }
+
+    fn tree_with(deposits: &[[u8; HASH_LEN]]) -> DepositTree {
+        let mut tree = DepositTree::new();
+        for deposit in deposits {
+            tree.push_leaf(*deposit).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn empty_root() {
+        assert_eq!(DepositTree::new().root(), expected_root(&[]));
+    }
+
+    #[test]
+    fn roots_match_merkleize() {
+        let deposits = deposits(20);
+        let mut tree = DepositTree::new();
+        for (i, deposit) in deposits.iter().enumerate() {
+            tree.push_leaf(*deposit).unwrap();
+            assert_eq!(tree.root(), expected_root(&deposits[..=i]));
+        }
+        assert_eq!(tree.deposit_count(), 20);
+    }
+
+    #[test]
+    fn proofs_are_valid() {
+        let deposits = deposits(13);
+        let mut tree = tree_with(&deposits);
+        tree.finalize(5, [1; HASH_LEN], 100).unwrap();
+        let root = tree.root();
+
+        for index in 5..13 {
+            let (leaf, proof) = tree.get_proof(index).unwrap();
+            assert_eq!(leaf, deposits[index as usize]);
+            assert!(is_valid_merkle_branch(
+                &leaf,
+                &proof,
+                DEPOSIT_CONTRACT_TREE_DEPTH + 1,
+                index,
+                &root
+            ));
+        }
+
+        for index in [0, 4, 13] {
+            assert_eq!(
+                tree.get_proof(index),
+                Err(DepositTreeError::InvalidProofIndex { index })
+            );
+        }
+    }
+
+    #[test]
+    fn finalization_preserves_root() {
+        let deposits = deposits(11);
+        let mut tree = tree_with(&deposits);
+        assert_eq!(tree.get_snapshot(), Err(DepositTreeError::NotFinalized));
+        assert_eq!(
+            DepositTreeError::NotFinalized.to_string(),
+            "no deposits have been finalized"
+        );
+
+        for finalized in [0, 1, 3, 4, 8, 11] {
+            tree.finalize(finalized, [2; HASH_LEN], finalized).unwrap();
+            assert_eq!(tree.finalized_deposit_count(), finalized);
+            assert_eq!(tree.root(), expected_root(&deposits));
+        }
+
+        assert_eq!(
+            tree.finalize(12, [2; HASH_LEN], 12),
+            Err(DepositTreeError::InvalidFinalization { deposit_count: 12 })
+        );
+        assert_eq!(
+            tree.finalize(10, [2; HASH_LEN], 10),
+            Err(DepositTreeError::InvalidFinalization { deposit_count: 10 })
+        );
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let deposits = deposits(16);
+        let mut tree = tree_with(&deposits[..7]);
+        tree.finalize(6, [3; HASH_LEN], 42).unwrap();
+
+        let snapshot = tree.get_snapshot().unwrap();
+        assert_eq!(snapshot.deposit_count, 6);
+        assert_eq!(snapshot.finalized.len(), 2);
+        assert_eq!(snapshot.deposit_root, expected_root(&deposits[..6]));
+        assert_eq!(snapshot.execution_block_hash, [3; HASH_LEN]);
+        assert_eq!(snapshot.execution_block_height, 42);
+
+        let mut restored = DepositTree::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.get_snapshot(), Ok(snapshot));
+        assert_eq!(restored.finalized_deposit_count(), 6);
+        for deposit in &deposits[6..] {
+            restored.push_leaf(*deposit).unwrap();
+        }
+        assert_eq!(restored.root(), expected_root(&deposits));
+
+        let (leaf, proof) = restored.get_proof(9).unwrap();
+        assert!(is_valid_merkle_branch(
+            &leaf,
+            &proof,
+            DEPOSIT_CONTRACT_TREE_DEPTH + 1,
+            9,
+            &restored.root()
+        ));
+    }
+
+    #[test]
+    fn invalid_snapshots() {
+        let mut tree = tree_with(&deposits(5));
+        tree.finalize(5, [4; HASH_LEN], 5).unwrap();
+        let snapshot = tree.get_snapshot().unwrap();
+
+        let mut wrong_root = snapshot.clone();
+        wrong_root.deposit_root[0] ^= 1;
+        assert_eq!(
+            DepositTree::from_snapshot(&wrong_root),
+            Err(DepositTreeError::InvalidSnapshot)
+        );
+
+        let mut missing_node = snapshot.clone();
+        missing_node.finalized.pop();
+        assert_eq!(missing_node.calculate_root(), None);
+        assert_eq!(
+            DepositTree::from_snapshot(&missing_node),
+            Err(DepositTreeError::InvalidSnapshot)
+        );
+    }
+}
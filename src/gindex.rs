@@ -0,0 +1,256 @@
+//! Generalized index arithmetic, as defined by the SSZ `merkle-proofs.md` specification.
+use core::fmt;
+use core::num::NonZeroU64;
+
+/// Index of a node in a binary Merkle tree.
+///
+/// The root has generalized index `1`, and the children of the node at index `i` are at `2 * i`
+/// and `2 * i + 1`. The depth of a node is therefore `floor(log2(i))`, and its position within its
+/// level is `i - 2^depth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GeneralizedIndex(NonZeroU64);
+
+impl GeneralizedIndex {
+    /// The root of the tree.
+    pub const ROOT: Self = Self(NonZeroU64::MIN);
+
+    /// Create a generalized index, returning `None` if `index` is zero.
+    pub const fn new(index: u64) -> Option<Self> {
+        match NonZeroU64::new(index) {
+            Some(index) => Some(Self(index)),
+            None => None,
+        }
+    }
+
+    /// The generalized index of the node at position `index` in level `depth` of the tree.
+    ///
+    /// Returns `None` if `index` doesn't fit in the level or the result doesn't fit in a `u64`.
+    pub fn from_depth_and_index(depth: u32, index: u64) -> Option<Self> {
+        let first = 1u64.checked_shl(depth)?;
+        if index >= first {
+            return None;
+        }
+        Self::new(first | index)
+    }
+
+    /// Concatenate generalized indices, each of which is relative to the subtree rooted at the
+    /// previous one.
+    ///
+    /// Returns `None` if the result doesn't fit in a `u64`.
+    ///
+    /// This matches the `concat_generalized_indices` function from the SSZ specification.
+    pub fn concat(indices: &[Self]) -> Option<Self> {
+        indices.iter().try_fold(Self::ROOT, |acc, index| {
+            let depth = index.depth();
+            if acc.as_u64().leading_zeros() < depth {
+                return None;
+            }
+            Self::new((acc.as_u64() << depth) | index.subtree_index())
+        })
+    }
+
+    /// The underlying integer value.
+    pub const fn as_u64(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Depth of the node, with the root at depth zero.
+    ///
+    /// This matches the `get_generalized_index_length` function from the SSZ specification.
+    pub const fn depth(self) -> u32 {
+        self.0.ilog2()
+    }
+
+    /// Position of the node within its level, counting from the left.
+    ///
+    /// This matches the `get_subtree_index` function from the consensus specification.
+    pub const fn subtree_index(self) -> u64 {
+        self.as_u64() ^ (1 << self.depth())
+    }
+
+    /// Whether the node is the root of the tree.
+    pub const fn is_root(self) -> bool {
+        self.as_u64() == 1
+    }
+
+    /// Whether the node is the right child of its parent.
+    pub const fn is_right(self) -> bool {
+        self.as_u64() & 1 == 1
+    }
+
+    /// The bit of the index at `position`, which determines the direction taken at depth
+    /// `depth() - position` on the path from the root.
+    ///
+    /// This matches the `get_generalized_index_bit` function from the SSZ specification.
+    pub const fn bit(self, position: u32) -> bool {
+        position < u64::BITS && (self.as_u64() >> position) & 1 == 1
+    }
+
+    /// The parent of the node, or `None` for the root.
+    pub const fn parent(self) -> Option<Self> {
+        Self::new(self.as_u64() >> 1)
+    }
+
+    /// The other child of the node's parent, or `None` for the root.
+    pub const fn sibling(self) -> Option<Self> {
+        if self.is_root() {
+            None
+        } else {
+            Self::new(self.as_u64() ^ 1)
+        }
+    }
+
+    /// The left (`right == false`) or right child of the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the child's index doesn't fit in a `u64`.
+    pub const fn child(self, right: bool) -> Self {
+        assert!(
+            self.as_u64() < 1 << (u64::BITS - 1),
+            "generalized index overflow"
+        );
+        match Self::new((self.as_u64() << 1) | right as u64) {
+            Some(child) => child,
+            None => unreachable!(),
+        }
+    }
+
+    /// Iterate over the path from this node up to, but excluding, the root.
+    pub fn path_to_root(self) -> impl Iterator<Item = Self> {
+        core::iter::successors(Some(self), |index| index.parent())
+            .take_while(|index| !index.is_root())
+    }
+
+    /// Iterate over the siblings of the nodes on the path from this node to the root, which is the
+    /// order of the nodes in a Merkle branch for this node.
+    pub fn branch(self) -> impl Iterator<Item = Self> {
+        self.path_to_root().filter_map(Self::sibling)
+    }
+}
+
+impl From<GeneralizedIndex> for u64 {
+    fn from(index: GeneralizedIndex) -> u64 {
+        index.as_u64()
+    }
+}
+
+impl TryFrom<u64> for GeneralizedIndex {
+    type Error = ZeroGeneralizedIndex;
+
+    fn try_from(index: u64) -> Result<Self, Self::Error> {
+        Self::new(index).ok_or(ZeroGeneralizedIndex)
+    }
+}
+
+impl fmt::Display for GeneralizedIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Error returned when converting zero to a `GeneralizedIndex`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZeroGeneralizedIndex;
+
+impl fmt::Display for ZeroGeneralizedIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("generalized indices start at 1")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ZeroGeneralizedIndex {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gindex(index: u64) -> GeneralizedIndex {
+        GeneralizedIndex::new(index).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let index = gindex(13);
+        assert_eq!(index.depth(), 3);
+        assert_eq!(index.subtree_index(), 5);
+        assert!(index.is_right());
+        assert_eq!(index.parent(), Some(gindex(6)));
+        assert_eq!(index.sibling(), Some(gindex(12)));
+        assert_eq!(index.child(false), gindex(26));
+        assert_eq!(index.child(true), gindex(27));
+        assert_eq!(
+            [0, 1, 2, 3].map(|position| index.bit(position)),
+            [true, false, true, true]
+        );
+
+        assert_eq!(GeneralizedIndex::ROOT.depth(), 0);
+        assert_eq!(GeneralizedIndex::ROOT.parent(), None);
+        assert_eq!(GeneralizedIndex::ROOT.sibling(), None);
+        assert_eq!(GeneralizedIndex::new(0), None);
+        assert_eq!(GeneralizedIndex::try_from(0), Err(ZeroGeneralizedIndex));
+        assert_eq!(
+            ZeroGeneralizedIndex.to_string(),
+            "generalized indices start at 1"
+        );
+    }
+
+    #[test]
+    fn from_depth_and_index() {
+        assert_eq!(
+            GeneralizedIndex::from_depth_and_index(0, 0),
+            Some(GeneralizedIndex::ROOT)
+        );
+        assert_eq!(
+            GeneralizedIndex::from_depth_and_index(3, 5),
+            Some(gindex(13))
+        );
+        assert_eq!(GeneralizedIndex::from_depth_and_index(3, 8), None);
+        assert_eq!(
+            GeneralizedIndex::from_depth_and_index(63, 0),
+            Some(gindex(1 << 63))
+        );
+        assert_eq!(GeneralizedIndex::from_depth_and_index(64, 0), None);
+    }
+
+    #[test]
+    fn paths() {
+        let index = gindex(13);
+        assert_eq!(
+            index.path_to_root().collect::<Vec<_>>(),
+            [gindex(13), gindex(6), gindex(3)]
+        );
+        assert_eq!(
+            index.branch().collect::<Vec<_>>(),
+            [gindex(12), gindex(7), gindex(2)]
+        );
+        assert_eq!(GeneralizedIndex::ROOT.path_to_root().count(), 0);
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(GeneralizedIndex::concat(&[]), Some(GeneralizedIndex::ROOT));
+        assert_eq!(GeneralizedIndex::concat(&[gindex(13)]), Some(gindex(13)));
+        // Node 3 of the subtree rooted at node 2: 2 -> 5.
+        assert_eq!(
+            GeneralizedIndex::concat(&[gindex(2), gindex(3)]),
+            Some(gindex(5))
+        );
+        // Node 5 (binary 101) of the subtree rooted at node 6 (binary 110) is binary 11001.
+        assert_eq!(
+            GeneralizedIndex::concat(&[gindex(6), gindex(5)]),
+            Some(gindex(0b11001))
+        );
+        assert_eq!(
+            GeneralizedIndex::concat(&[gindex(1 << 40), gindex(1 << 30)]),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn child_overflow() {
+        gindex(1 << 63).child(false);
+    }
+}
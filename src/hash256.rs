@@ -0,0 +1,270 @@
+//! A 32-byte SHA256 digest with hex formatting and parsing.
+use crate::HASH_LEN;
+use core::fmt;
+use core::str::FromStr;
+
+/// A SHA256 digest, formatted and parsed as `0x`-prefixed lowercase hex.
+///
+/// The type is a transparent wrapper around `[u8; HASH_LEN]`, so conversions in either direction
+/// are free, and `Hash256::from_array_ref` reinterprets a borrowed array without copying.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(transparent)]
+pub struct Hash256(pub [u8; HASH_LEN]);
+
+/// Error returned when parsing a `Hash256` from a hex string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseHash256Error {
+    /// The string doesn't contain exactly `2 * HASH_LEN` hex digits after the optional `0x`.
+    InvalidLength { found: usize },
+    /// The string contains a character that isn't a hex digit.
+    InvalidCharacter { index: usize },
+}
+
+impl fmt::Display for ParseHash256Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidLength { found } => {
+                write!(f, "expected {} hex digits, found {found}", 2 * HASH_LEN)
+            }
+            Self::InvalidCharacter { index } => {
+                write!(f, "invalid hex character at index {index}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseHash256Error {}
+
+impl Hash256 {
+    /// The all-zero hash.
+    pub const ZERO: Self = Self([0; HASH_LEN]);
+
+    /// Wrap a digest.
+    pub const fn new(bytes: [u8; HASH_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// Borrow a digest as a `Hash256` without copying it.
+    pub const fn from_array_ref(bytes: &[u8; HASH_LEN]) -> &Self {
+        // SAFETY: `Hash256` is `repr(transparent)` over `[u8; HASH_LEN]`.
+        unsafe { &*(bytes as *const [u8; HASH_LEN] as *const Self) }
+    }
+
+    /// Copy a digest from a slice, returning `None` if it isn't `HASH_LEN` bytes long.
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(Self)
+    }
+
+    /// The digest bytes.
+    pub const fn as_bytes(&self) -> &[u8; HASH_LEN] {
+        &self.0
+    }
+
+    /// Unwrap the digest.
+    pub const fn into_bytes(self) -> [u8; HASH_LEN] {
+        self.0
+    }
+}
+
+impl From<[u8; HASH_LEN]> for Hash256 {
+    fn from(bytes: [u8; HASH_LEN]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Hash256> for [u8; HASH_LEN] {
+    fn from(hash: Hash256) -> Self {
+        hash.0
+    }
+}
+
+impl<'a> From<&'a [u8; HASH_LEN]> for &'a Hash256 {
+    fn from(bytes: &'a [u8; HASH_LEN]) -> Self {
+        Hash256::from_array_ref(bytes)
+    }
+}
+
+impl AsRef<[u8]> for Hash256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8; HASH_LEN]> for Hash256 {
+    fn as_ref(&self) -> &[u8; HASH_LEN] {
+        &self.0
+    }
+}
+
+impl AsMut<[u8]> for Hash256 {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl fmt::LowerHex for Hash256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("0x")?;
+        }
+        f.write_str(encode_hex(&self.0, &mut [0; 2 * HASH_LEN]))
+    }
+}
+
+impl fmt::Display for Hash256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:#x}")
+    }
+}
+
+impl fmt::Debug for Hash256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:#x}")
+    }
+}
+
+impl FromStr for Hash256 {
+    type Err = ParseHash256Error;
+
+    /// Parse a hex digest, with or without a `0x` prefix. Both cases of hex digit are accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (offset, digits) = match s.strip_prefix("0x") {
+            Some(digits) => (2, digits.as_bytes()),
+            None => (0, s.as_bytes()),
+        };
+        if digits.len() != 2 * HASH_LEN {
+            return Err(ParseHash256Error::InvalidLength {
+                found: digits.len(),
+            });
+        }
+
+        let mut bytes = [0; HASH_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let nibble = |j: usize| {
+                (digits[j] as char)
+                    .to_digit(16)
+                    .map(|digit| digit as u8)
+                    .ok_or(ParseHash256Error::InvalidCharacter { index: offset + j })
+            };
+            *byte = (nibble(2 * i)? << 4) | nibble(2 * i + 1)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// Write the lowercase hex encoding of `bytes` to `out`.
+fn encode_hex<'a>(bytes: &[u8; HASH_LEN], out: &'a mut [u8; 2 * HASH_LEN]) -> &'a str {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    for (byte, pair) in bytes.iter().zip(out.chunks_exact_mut(2)) {
+        pair[0] = DIGITS[(byte >> 4) as usize];
+        pair[1] = DIGITS[(byte & 0xf) as usize];
+    }
+    // Only ASCII hex digits were written.
+    core::str::from_utf8(out).unwrap()
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use serde::de::{self, Deserialize, Deserializer, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    impl Serialize for Hash256 {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut out = [0; 2 + 2 * HASH_LEN];
+            out[..2].copy_from_slice(b"0x");
+            let digits: &mut [u8; 2 * HASH_LEN] = (&mut out[2..]).try_into().unwrap();
+            encode_hex(&self.0, digits);
+            // Only ASCII was written.
+            serializer.serialize_str(core::str::from_utf8(&out).unwrap())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Hash256 {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_str(Hash256Visitor)
+        }
+    }
+
+    struct Hash256Visitor;
+
+    impl Visitor<'_> for Hash256Visitor {
+        type Value = Hash256;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a 0x-prefixed hex string of 32 bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Hash256, E> {
+            if !value.starts_with("0x") {
+                return Err(E::invalid_value(de::Unexpected::Str(value), &self));
+            }
+            value
+                .parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABC: &str = "0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn hex_round_trip() {
+        let hash: Hash256 = ABC.parse().unwrap();
+        assert_eq!(hash, Hash256(crate::hash_fixed(b"abc")));
+        assert_eq!(hash.to_string(), ABC);
+        assert_eq!(format!("{hash:?}"), ABC);
+        assert_eq!(format!("{hash:x}"), &ABC[2..]);
+        assert_eq!(ABC[2..].to_uppercase().parse(), Ok(hash));
+        assert_eq!(Hash256::ZERO.to_string(), format!("0x{}", "0".repeat(64)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "0x1234".parse::<Hash256>(),
+            Err(ParseHash256Error::InvalidLength { found: 4 })
+        );
+        let mut invalid = ABC.to_owned();
+        invalid.replace_range(10..11, "g");
+        assert_eq!(
+            invalid.parse::<Hash256>(),
+            Err(ParseHash256Error::InvalidCharacter { index: 10 })
+        );
+        assert_eq!(
+            ParseHash256Error::InvalidLength { found: 4 }.to_string(),
+            "expected 64 hex digits, found 4"
+        );
+        assert_eq!(
+            ParseHash256Error::InvalidCharacter { index: 10 }.to_string(),
+            "invalid hex character at index 10"
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let bytes = [7; HASH_LEN];
+        let hash = Hash256::from(bytes);
+        assert_eq!(<[u8; HASH_LEN]>::from(hash), bytes);
+        assert_eq!(Hash256::from_array_ref(&bytes), &hash);
+        assert_eq!(AsRef::<[u8]>::as_ref(&hash), &bytes[..]);
+        assert_eq!(Hash256::from_slice(&bytes), Some(hash));
+        assert_eq!(Hash256::from_slice(&bytes[1..]), None);
+        assert!(Hash256::ZERO < hash);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let hash: Hash256 = ABC.parse().unwrap();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{ABC}\""));
+        assert_eq!(serde_json::from_str::<Hash256>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<Hash256>(&format!("\"{}\"", &ABC[2..])).is_err());
+    }
+}
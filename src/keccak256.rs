@@ -0,0 +1,514 @@
+//! Keccak-256, the hash function used by the execution layer.
+//!
+//! This is the original Keccak submission with output length 256 bits, as used for addresses,
+//! event topics and EIP-712 signing, not the standardised SHA3-256 which pads differently.
+//!
+//! The structure mirrors the SHA256 API at the crate root: `Keccak256` and `Keccak256Context`
+//! are implemented by each backend, and `DynamicImpl` picks the best backend at runtime. The
+//! SHA256 backend selection (`Backend::force`, `self_test`) doesn't apply to Keccak.
+use alloc::vec::Vec;
+
+#[cfg(target_arch = "aarch64")]
+mod armv8_impl;
+
+#[cfg(target_arch = "aarch64")]
+pub use armv8_impl::{Armv8Sha3Context, Armv8Sha3Impl};
+
+pub use self::DynamicContext as Context;
+
+/// Length of a Keccak-256 hash in bytes.
+pub const HASH_LEN: usize = 32;
+
+/// Bytes absorbed per permutation: the 1600-bit state minus twice the output length.
+const RATE: usize = 200 - 2 * HASH_LEN;
+
+/// Returns the digest of `input` using the best available implementation.
+pub fn hash(input: &[u8]) -> Vec<u8> {
+    DynamicImpl::best().hash(input)
+}
+
+/// Hash function returning a fixed-size array (to save on allocations).
+///
+/// Uses the best available implementation based on CPU features.
+pub fn hash_fixed(input: &[u8]) -> [u8; HASH_LEN] {
+    DynamicImpl::best().hash_fixed(input)
+}
+
+/// Hash `input`, writing the digest to `out`.
+///
+/// Uses the best available implementation based on CPU features.
+pub fn hash_into(input: &[u8], out: &mut [u8; HASH_LEN]) {
+    DynamicImpl::best().hash_into(input, out)
+}
+
+/// Context trait for abstracting over implementation contexts.
+///
+/// Like `Sha256Context`, contexts can be cloned to hash several messages with a common prefix.
+pub trait Keccak256Context: Clone {
+    fn new() -> Self;
+
+    fn update(&mut self, bytes: &[u8]);
+
+    fn finalize(self) -> [u8; HASH_LEN];
+
+    /// Like `finalize`, but writing the digest to `out`.
+    fn finalize_into(self, out: &mut [u8; HASH_LEN]) {
+        *out = self.finalize();
+    }
+
+    /// Discard all input, returning the context to its initial state.
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Return the digest of the input so far and reset the context.
+    fn finalize_reset(&mut self) -> [u8; HASH_LEN] {
+        core::mem::replace(self, Self::new()).finalize()
+    }
+}
+
+/// Top-level trait implemented by the Keccak-256 backends.
+pub trait Keccak256 {
+    type Context: Keccak256Context;
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        self.hash_fixed(input).to_vec()
+    }
+
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        let mut ctxt = Self::Context::new();
+        ctxt.update(input);
+        ctxt.finalize()
+    }
+
+    /// Hash `input`, writing the digest to `out`.
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        *out = self.hash_fixed(input);
+    }
+}
+
+/// Portable implementation of Keccak-256 in pure Rust.
+pub struct PortableImpl;
+
+/// Streaming context for `PortableImpl`.
+#[derive(Clone)]
+pub struct PortableContext {
+    sponge: Sponge,
+}
+
+impl Keccak256Context for PortableContext {
+    fn new() -> Self {
+        Self {
+            sponge: Sponge::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.sponge.absorb(bytes, keccak_f_portable);
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        self.sponge.finalize(keccak_f_portable)
+    }
+}
+
+impl Keccak256 for PortableImpl {
+    type Context = PortableContext;
+}
+
+/// Default dynamic implementation that switches between available implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicImpl {
+    #[cfg(target_arch = "aarch64")]
+    Armv8Sha3,
+    Portable,
+}
+
+// Runtime latch for detecting the availability of the SHA3 crypto extensions on aarch64.
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(aarch64_sha3_extensions, "sha3");
+
+#[inline(always)]
+pub fn have_sha3_extensions() -> bool {
+    #[cfg(target_arch = "aarch64")]
+    return aarch64_sha3_extensions::get();
+
+    #[cfg(not(target_arch = "aarch64"))]
+    return false;
+}
+
+impl DynamicImpl {
+    /// Choose the best available implementation based on the currently executing CPU.
+    #[inline(always)]
+    pub fn best() -> Self {
+        #[cfg(target_arch = "aarch64")]
+        if have_sha3_extensions() {
+            return Self::Armv8Sha3;
+        }
+
+        Self::Portable
+    }
+}
+
+impl Keccak256 for DynamicImpl {
+    type Context = DynamicContext;
+
+    #[inline(always)]
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        match self {
+            #[cfg(target_arch = "aarch64")]
+            Self::Armv8Sha3 => Armv8Sha3Impl.hash(input),
+            Self::Portable => PortableImpl.hash(input),
+        }
+    }
+
+    #[inline(always)]
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        match self {
+            #[cfg(target_arch = "aarch64")]
+            Self::Armv8Sha3 => Armv8Sha3Impl.hash_fixed(input),
+            Self::Portable => PortableImpl.hash_fixed(input),
+        }
+    }
+
+    #[inline(always)]
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        match self {
+            #[cfg(target_arch = "aarch64")]
+            Self::Armv8Sha3 => Armv8Sha3Impl.hash_into(input, out),
+            Self::Portable => PortableImpl.hash_into(input, out),
+        }
+    }
+}
+
+/// Context encapsulating all implementation contexts.
+#[derive(Clone)]
+pub enum DynamicContext {
+    #[cfg(target_arch = "aarch64")]
+    Armv8Sha3(Armv8Sha3Context),
+    Portable(PortableContext),
+}
+
+impl Keccak256Context for DynamicContext {
+    fn new() -> Self {
+        match DynamicImpl::best() {
+            #[cfg(target_arch = "aarch64")]
+            DynamicImpl::Armv8Sha3 => Self::Armv8Sha3(Armv8Sha3Context::new()),
+            DynamicImpl::Portable => Self::Portable(PortableContext::new()),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            #[cfg(target_arch = "aarch64")]
+            Self::Armv8Sha3(ctxt) => Keccak256Context::update(ctxt, bytes),
+            Self::Portable(ctxt) => Keccak256Context::update(ctxt, bytes),
+        }
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        match self {
+            #[cfg(target_arch = "aarch64")]
+            Self::Armv8Sha3(ctxt) => Keccak256Context::finalize(ctxt),
+            Self::Portable(ctxt) => Keccak256Context::finalize(ctxt),
+        }
+    }
+}
+
+/// The Keccak sponge. Each context passes its implementation of the permutation to every call.
+#[derive(Clone)]
+struct Sponge {
+    state: [u64; 25],
+    buffer: [u8; RATE],
+    buffer_len: usize,
+}
+
+impl Sponge {
+    fn new() -> Self {
+        Self {
+            state: [0; 25],
+            buffer: [0; RATE],
+            buffer_len: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn absorb(&mut self, mut bytes: &[u8], keccak_f: impl Fn(&mut [u64; 25])) {
+        if self.buffer_len > 0 {
+            let take = bytes.len().min(RATE - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&bytes[..take]);
+            self.buffer_len += take;
+            bytes = &bytes[take..];
+            if self.buffer_len < RATE {
+                return;
+            }
+            let block = self.buffer;
+            self.absorb_block(&block, &keccak_f);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = bytes.chunks_exact(RATE);
+        for block in &mut blocks {
+            self.absorb_block(block.try_into().unwrap(), &keccak_f);
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    #[inline(always)]
+    fn absorb_block(&mut self, block: &[u8; RATE], keccak_f: impl Fn(&mut [u64; 25])) {
+        for (lane, word) in self.state.iter_mut().zip(block.chunks_exact(8)) {
+            *lane ^= u64::from_le_bytes(word.try_into().unwrap());
+        }
+        keccak_f(&mut self.state);
+    }
+
+    #[inline(always)]
+    fn finalize(mut self, keccak_f: impl Fn(&mut [u64; 25])) -> [u8; HASH_LEN] {
+        // Keccak padding: a set bit after the message and at the end of the block, which may be
+        // the same byte.
+        let mut block = [0; RATE];
+        block[..self.buffer_len].copy_from_slice(&self.buffer[..self.buffer_len]);
+        block[self.buffer_len] = 0x01;
+        block[RATE - 1] |= 0x80;
+        self.absorb_block(&block, keccak_f);
+
+        let mut output = [0; HASH_LEN];
+        for (bytes, lane) in output.chunks_exact_mut(8).zip(self.state) {
+            bytes.copy_from_slice(&lane.to_le_bytes());
+        }
+        output
+    }
+}
+
+/// Round constants for the iota step.
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+fn keccak_f_portable(state: &mut [u64; 25]) {
+    // Safety: the `u64` operations don't require any CPU feature.
+    unsafe { keccak_f::<u64>(state) }
+}
+
+/// Operations on a 64-bit lane of the Keccak state, named after the ARMv8 SHA3 instructions.
+///
+/// All methods are `unsafe` because they may require the corresponding CPU feature, and must be
+/// inlined into a function compiled with that feature enabled.
+trait Lane: Copy {
+    unsafe fn load(word: u64) -> Self;
+    unsafe fn store(self) -> u64;
+
+    unsafe fn xor(self, other: Self) -> Self;
+    /// Computes `self ^ b ^ c`.
+    unsafe fn eor3(self, b: Self, c: Self) -> Self;
+    /// Computes `self ^ b.rotate_left(1)`.
+    unsafe fn rax1(self, b: Self) -> Self;
+    /// Computes `(self ^ b).rotate_right(RIGHT)`.
+    unsafe fn xar<const RIGHT: i32>(self, b: Self) -> Self;
+    /// Computes `self ^ (b & !c)`.
+    unsafe fn bcax(self, b: Self, c: Self) -> Self;
+}
+
+impl Lane for u64 {
+    #[inline(always)]
+    unsafe fn load(word: u64) -> Self {
+        word
+    }
+
+    #[inline(always)]
+    unsafe fn store(self) -> u64 {
+        self
+    }
+
+    #[inline(always)]
+    unsafe fn xor(self, other: Self) -> Self {
+        self ^ other
+    }
+
+    #[inline(always)]
+    unsafe fn eor3(self, b: Self, c: Self) -> Self {
+        self ^ b ^ c
+    }
+
+    #[inline(always)]
+    unsafe fn rax1(self, b: Self) -> Self {
+        self ^ b.rotate_left(1)
+    }
+
+    #[inline(always)]
+    unsafe fn xar<const RIGHT: i32>(self, b: Self) -> Self {
+        (self ^ b).rotate_right(RIGHT as u32)
+    }
+
+    #[inline(always)]
+    unsafe fn bcax(self, b: Self, c: Self) -> Self {
+        self ^ (b & !c)
+    }
+}
+
+/// The Keccak-f[1600] permutation, with lane `x + 5 * y` of the state at `state[x + 5 * y]`.
+#[inline(always)]
+unsafe fn keccak_f<L: Lane>(state: &mut [u64; 25]) {
+    let mut a = state.map(|word| L::load(word));
+
+    for rc in ROUND_CONSTANTS {
+        // Theta: XOR each lane with the parities of two neighbouring columns.
+        let c: [L; 5] =
+            core::array::from_fn(|x| a[x].eor3(a[x + 5], a[x + 10]).eor3(a[x + 15], a[x + 20]));
+        let d: [L; 5] = core::array::from_fn(|x| c[(x + 4) % 5].rax1(c[(x + 1) % 5]));
+
+        // Theta combined with rho (rotations) and pi (lane permutation). `b[X + 5 * Y]` is lane
+        // `x + 5 * y` of `a` for `X = y` and `Y = 2 * x + 3 * y`, rotated left by the rho
+        // offset, which is a right rotation by 64 minus the offset.
+        let b = [
+            a[0].xar::<0>(d[0]),
+            a[6].xar::<20>(d[1]),
+            a[12].xar::<21>(d[2]),
+            a[18].xar::<43>(d[3]),
+            a[24].xar::<50>(d[4]),
+            a[3].xar::<36>(d[3]),
+            a[9].xar::<44>(d[4]),
+            a[10].xar::<61>(d[0]),
+            a[16].xar::<19>(d[1]),
+            a[22].xar::<3>(d[2]),
+            a[1].xar::<63>(d[1]),
+            a[7].xar::<58>(d[2]),
+            a[13].xar::<39>(d[3]),
+            a[19].xar::<56>(d[4]),
+            a[20].xar::<46>(d[0]),
+            a[4].xar::<37>(d[4]),
+            a[5].xar::<28>(d[0]),
+            a[11].xar::<54>(d[1]),
+            a[17].xar::<49>(d[2]),
+            a[23].xar::<8>(d[3]),
+            a[2].xar::<2>(d[2]),
+            a[8].xar::<9>(d[3]),
+            a[14].xar::<25>(d[4]),
+            a[15].xar::<23>(d[0]),
+            a[21].xar::<62>(d[1]),
+        ];
+
+        // Chi: combine each lane with the next two in its row.
+        a = core::array::from_fn(|i| {
+            let (x, row) = (i % 5, i - i % 5);
+            b[i].bcax(b[row + (x + 2) % 5], b[row + (x + 1) % 5])
+        });
+
+        // Iota.
+        a[0] = a[0].xor(L::load(rc));
+    }
+
+    *state = a.map(|lane| lane.store());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_hex::FromHex;
+
+    /// Digests of the first `len` bytes of `0, 1, 2, ...`, around multiples of the rate.
+    const COUNTING_VECTORS: [(usize, &str); 8] = [
+        (
+            0,
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+        ),
+        (
+            3,
+            "f84a97f1f0a956e738abd85c2e0a5026f8874e3ec09c8f012159dfeeaab2b156",
+        ),
+        (
+            135,
+            "cbdfd9dee5faad3818d6b06f95a219fd290b0e1706f6a82e5a595b9ce9faca62",
+        ),
+        (
+            136,
+            "7ce759f1ab7f9ce437719970c26b0a66ff11fe3e38e17df89cf5d29c7d7f807e",
+        ),
+        (
+            137,
+            "ac73d4fae68b8453f764007c1a20ce95994187861f0c3227a3a8e99a73a3b1db",
+        ),
+        (
+            200,
+            "bfb0aa97863e797943cf7c33bb7e880bb4543f3d2703c0923c6901c2af57b890",
+        ),
+        (
+            272,
+            "fdf2ec49e749960d3c8521a0219af8d03e30e2b3bf19bd16150ee0eaf133d66e",
+        ),
+        (
+            300,
+            "a679e749a6af300c36e7ff2255d220864eab27b382f9cfdc5aa4d13563ba36ff",
+        ),
+    ];
+
+    fn counting(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn known_answers() {
+        let expected: Vec<u8> = "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+            .from_hex()
+            .unwrap();
+        assert_eq!(hash(b"abc"), expected);
+
+        for (len, expected) in COUNTING_VECTORS {
+            let expected: Vec<u8> = expected.from_hex().unwrap();
+            let input = counting(len);
+            assert_eq!(hash(&input), expected, "{len}");
+            assert_eq!(PortableImpl.hash(&input), expected, "{len}");
+            #[cfg(target_arch = "aarch64")]
+            assert_eq!(Armv8Sha3Impl.hash(&input), expected, "{len}");
+        }
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let input = counting(300);
+        for (len, _) in COUNTING_VECTORS {
+            let input = &input[..len];
+            let expected = PortableImpl.hash_fixed(input);
+
+            let mut out = [0; HASH_LEN];
+            hash_into(input, &mut out);
+            assert_eq!(out, expected, "{len}");
+
+            for chunk_size in [1, 7, 64, RATE, 200] {
+                let mut ctxt = Context::new();
+                for chunk in input.chunks(chunk_size) {
+                    ctxt.update(chunk);
+                }
+                let forked = ctxt.clone();
+                assert_eq!(ctxt.finalize_reset(), expected, "{len} {chunk_size}");
+                assert_eq!(forked.finalize(), expected, "{len} {chunk_size}");
+                assert_eq!(ctxt.finalize(), hash_fixed(&[]));
+            }
+        }
+    }
+}
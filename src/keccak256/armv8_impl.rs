@@ -0,0 +1,95 @@
+use super::{
+    have_sha3_extensions, keccak_f, keccak_f_portable, Keccak256, Keccak256Context, Lane, Sponge,
+    HASH_LEN,
+};
+use core::arch::aarch64::*;
+
+/// Implementation of Keccak-256 using the ARMv8.2 SHA3 crypto extensions.
+///
+/// Falls back to the portable permutation on CPUs without the extensions.
+pub struct Armv8Sha3Impl;
+
+/// Streaming context for `Armv8Sha3Impl`.
+#[derive(Clone)]
+pub struct Armv8Sha3Context {
+    sponge: Sponge,
+}
+
+impl Keccak256Context for Armv8Sha3Context {
+    fn new() -> Self {
+        Self {
+            sponge: Sponge::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.sponge.absorb(bytes, keccak_f_armv8);
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        self.sponge.finalize(keccak_f_armv8)
+    }
+}
+
+impl Keccak256 for Armv8Sha3Impl {
+    type Context = Armv8Sha3Context;
+}
+
+fn keccak_f_armv8(state: &mut [u64; 25]) {
+    if have_sha3_extensions() {
+        // Safety: SHA3 availability was checked above.
+        unsafe { keccak_f_sha3(state) }
+    } else {
+        keccak_f_portable(state)
+    }
+}
+
+#[target_feature(enable = "sha3")]
+unsafe fn keccak_f_sha3(state: &mut [u64; 25]) {
+    keccak_f::<uint64x2_t>(state)
+}
+
+/// Each lane is held in both halves of a vector register, of which only the low half is stored.
+///
+/// The SHA3 instructions are `#[inline]` rather than `#[inline(always)]`, which isn't allowed
+/// together with `#[target_feature]`, but are still inlined into `keccak_f_sha3`.
+impl Lane for uint64x2_t {
+    #[inline(always)]
+    unsafe fn load(word: u64) -> Self {
+        vdupq_n_u64(word)
+    }
+
+    #[inline(always)]
+    unsafe fn store(self) -> u64 {
+        vgetq_lane_u64::<0>(self)
+    }
+
+    #[inline(always)]
+    unsafe fn xor(self, other: Self) -> Self {
+        veorq_u64(self, other)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sha3")]
+    unsafe fn eor3(self, b: Self, c: Self) -> Self {
+        veor3q_u64(self, b, c)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sha3")]
+    unsafe fn rax1(self, b: Self) -> Self {
+        vrax1q_u64(self, b)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sha3")]
+    unsafe fn xar<const RIGHT: i32>(self, b: Self) -> Self {
+        vxarq_u64::<RIGHT>(self, b)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sha3")]
+    unsafe fn bcax(self, b: Self, c: Self) -> Self {
+        vbcaxq_u64(self, b, c)
+    }
+}
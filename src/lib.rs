@@ -5,17 +5,92 @@
 //! once in this crate made it easy to replace.
 //!
 //! Now this crate serves primarily as a wrapper over two SHA256 crates: `sha2` and `ring` – which
-//! it switches between at runtime based on the availability of SHA intrinsics.
+//! it switches between at runtime based on the availability of SHA intrinsics. On x86_64 CPUs
+//! without SHA intrinsics, batches of 64-byte messages are hashed in parallel using AVX2 or
+//! AVX-512 (with the `avx512` feature, which requires Rust 1.89) where available.
+//!
+//! The automatic choice can be overridden with `Backend::force` or the `ETHEREUM_HASHING_BACKEND`
+//! environment variable.
+//!
+//! The crate is `no_std` (but requires `alloc`) without the default `std` feature. Disabling the
+//! default `ring` feature avoids building `ring`'s C and assembly code, in which case a portable
+//! pure-Rust implementation is used on targets not supported by `sha2`. On aarch64, the SHA2
+//! crypto extensions are used through intrinsics rather than the `sha2` crate, whose support for
+//! them requires its assembly.
+//!
+//! Keccak-256, as used by the execution layer, is provided by the `keccak256` module with the
+//! same structure.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+mod armv8_impl;
+mod avx_impl;
+mod backend;
+mod compress;
+#[cfg(feature = "zero_hash_cache")]
+mod deposit_tree;
+mod gindex;
+mod hash256;
+pub mod keccak256;
+#[cfg(feature = "zero_hash_cache")]
+mod merkle;
+#[cfg(feature = "zero_hash_cache")]
+mod merkleizer;
+mod midstate;
+mod multiproof;
+mod pack;
+mod portable_impl;
+mod self_test;
 mod sha2_impl;
+mod shuffle;
 
 pub use self::DynamicContext as Context;
 
+pub use backend::{Backend, BackendError, BACKEND_ENV_VAR};
+pub use compress::{compress256, SHA256_IV};
+#[cfg(feature = "zero_hash_cache")]
+pub use deposit_tree::{
+    DepositTree, DepositTreeError, DepositTreeSnapshot, DEPOSIT_CONTRACT_TREE_DEPTH,
+};
+pub use gindex::{GeneralizedIndex, ZeroGeneralizedIndex};
+pub use hash256::{Hash256, ParseHash256Error};
+#[cfg(all(feature = "zero_hash_cache", feature = "rayon"))]
+pub use merkle::merkleize_parallel;
+#[cfg(feature = "zero_hash_cache")]
+pub use merkle::{
+    is_valid_merkle_branch, is_valid_normalized_merkle_branch, merkle_branch,
+    merkle_root_from_branch, merkleize,
+};
+#[cfg(feature = "zero_hash_cache")]
+pub use merkleizer::{LimitExceeded, Merkleizer};
+pub use midstate::Midstate;
+#[cfg(feature = "zero_hash_cache")]
+pub use multiproof::merkle_multiproof;
+pub use multiproof::{
+    calculate_multi_merkle_root, get_helper_indices, verify_merkle_multiproof, MultiproofError,
+};
+#[cfg(feature = "zero_hash_cache")]
+pub use pack::merkleize_packed;
+pub use pack::{pack, packed_chunk_count, packed_chunks, PackedChunks, PackedValue};
+pub use portable_impl::{hash32_concat_const, hash_const, PortableContext, PortableImpl};
+#[cfg(feature = "lazy_self_test")]
+pub use self_test::lazy_self_test_result;
+pub use self_test::{self_test, SelfTestError};
+pub use shuffle::{compute_shuffled_index, shuffle_list};
+
+#[cfg(target_arch = "aarch64")]
+pub use armv8_impl::{Armv8Sha2Context, Armv8Sha2Impl};
+#[cfg(target_arch = "x86_64")]
+pub use avx_impl::{Avx2Impl, Avx512Impl};
+
+// The implementation behind the `Sha2` backend.
+#[cfg(target_arch = "aarch64")]
+use armv8_impl::Armv8Sha2Impl as Sha2Impl;
 #[cfg(target_arch = "x86_64")]
-use sha2_impl::Sha2CrateImpl;
+use sha2_impl::Sha2CrateImpl as Sha2Impl;
 
-#[cfg(feature = "zero_hash_cache")]
-use std::sync::LazyLock;
+use alloc::vec::Vec;
 
 /// Length of a SHA256 hash in bytes.
 pub const HASH_LEN: usize = 32;
@@ -32,35 +107,166 @@ pub fn hash_fixed(input: &[u8]) -> [u8; HASH_LEN] {
     DynamicImpl::best().hash_fixed(input)
 }
 
+/// Hash `input`, writing the digest to `out`.
+///
+/// Uses the best available implementation based on CPU features.
+pub fn hash_into(input: &[u8], out: &mut [u8; HASH_LEN]) {
+    DynamicImpl::best().hash_into(input, out)
+}
+
+/// Like `hash_fixed`, but returning a `Hash256`.
+pub fn hash256(input: &[u8]) -> Hash256 {
+    Hash256(hash_fixed(input))
+}
+
+/// Hash a single 64-byte message, such as the concatenation of two 32-byte nodes.
+///
+/// Uses the best available implementation based on CPU features.
+pub fn hash_64(input: &[u8; 64]) -> [u8; HASH_LEN] {
+    DynamicImpl::best().hash_64(input)
+}
+
 /// Compute the hash of two slices concatenated.
+///
+/// Two 32-byte inputs are hashed with `hash_64`.
 pub fn hash32_concat(h1: &[u8], h2: &[u8]) -> [u8; 32] {
+    if let (Ok(h1), Ok(h2)) = (<&[u8; 32]>::try_from(h1), <&[u8; 32]>::try_from(h2)) {
+        let mut input = [0; 64];
+        input[..32].copy_from_slice(h1);
+        input[32..].copy_from_slice(h2);
+        return hash_64(&input);
+    }
+
     let mut ctxt = DynamicContext::new();
     ctxt.update(h1);
     ctxt.update(h2);
     ctxt.finalize()
 }
 
+/// Like `hash32_concat`, but returning a `Hash256`.
+pub fn hash256_concat(h1: &[u8], h2: &[u8]) -> Hash256 {
+    Hash256(hash32_concat(h1, h2))
+}
+
+/// Mix a length into `root`, as done for SSZ lists and bitlists.
+///
+/// The length is encoded as a little-endian integer, zero-padded to 32 bytes.
+pub fn mix_in_length(root: &[u8; HASH_LEN], length: usize) -> [u8; HASH_LEN] {
+    mix_in_length_u64(root, length as u64)
+}
+
+/// Mix a `u64` length into `root`, as done for SSZ lists and bitlists.
+pub fn mix_in_length_u64(root: &[u8; HASH_LEN], length: u64) -> [u8; HASH_LEN] {
+    hash32_concat(root, &length_chunk(length))
+}
+
+/// The chunk mixed into a root by `mix_in_length_u64`: `length` as a little-endian integer,
+/// zero-padded to 32 bytes.
+pub fn length_chunk(length: u64) -> [u8; HASH_LEN] {
+    let mut chunk = [0; HASH_LEN];
+    chunk[..8].copy_from_slice(&length.to_le_bytes());
+    chunk
+}
+
+/// Mix a union selector into `root`, as done for SSZ unions.
+///
+/// Valid selectors are less than 128.
+pub fn mix_in_selector(root: &[u8; HASH_LEN], selector: u8) -> [u8; HASH_LEN] {
+    let mut selector_chunk = [0; HASH_LEN];
+    selector_chunk[0] = selector;
+    hash32_concat(root, &selector_chunk)
+}
+
+/// Mix an auxiliary root into `root`.
+pub fn mix_in_aux(root: &[u8; HASH_LEN], aux: &[u8; HASH_LEN]) -> [u8; HASH_LEN] {
+    hash32_concat(root, aux)
+}
+
+/// Compute `hash32_concat` for a batch of 64-byte `[h1 || h2]` pairs.
+///
+/// The digest of `pairs[i]` is written to `output[i]`. Backend selection happens once for the
+/// whole batch, which lets implementations that support it hash several pairs in parallel.
+///
+/// # Panics
+///
+/// Panics if `pairs` and `output` have different lengths.
+pub fn hash32_concat_batch(pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+    DynamicImpl::best().hash32_concat_batch(pairs, output)
+}
+
 /// Context trait for abstracting over implementation contexts.
-pub trait Sha256Context {
+///
+/// Contexts can be cloned, so a context that has absorbed a common prefix can be forked to hash
+/// several messages starting with that prefix.
+pub trait Sha256Context: Clone {
     fn new() -> Self;
 
     fn update(&mut self, bytes: &[u8]);
 
     fn finalize(self) -> [u8; HASH_LEN];
+
+    /// Like `finalize`, but writing the digest to `out`.
+    fn finalize_into(self, out: &mut [u8; HASH_LEN]) {
+        *out = self.finalize();
+    }
+
+    /// Like `finalize`, but returning a `Hash256`.
+    fn finalize_hash256(self) -> Hash256 {
+        Hash256(self.finalize())
+    }
+
+    /// Discard all input, returning the context to its initial state.
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Return the digest of the input so far and reset the context.
+    fn finalize_reset(&mut self) -> [u8; HASH_LEN] {
+        core::mem::replace(self, Self::new()).finalize()
+    }
 }
 
-/// Top-level trait implemented by both `sha2` and `ring` implementations.
+/// Top-level trait implemented by the `sha2`, `ring` and portable implementations.
 pub trait Sha256 {
     type Context: Sha256Context;
 
     fn hash(&self, input: &[u8]) -> Vec<u8>;
 
     fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN];
+
+    /// Hash `input`, writing the digest to `out`.
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        *out = self.hash_fixed(input);
+    }
+
+    /// Hash a single 64-byte message.
+    ///
+    /// Implementations can use the fixed length to skip buffering and append a precomputed
+    /// padding block.
+    fn hash_64(&self, input: &[u8; 64]) -> [u8; HASH_LEN] {
+        self.hash_fixed(input)
+    }
+
+    /// Hash each 64-byte pair in `pairs`, writing the digest of `pairs[i]` to `output[i]`.
+    ///
+    /// The default implementation hashes one pair at a time with `hash_64`.
+    fn hash32_concat_batch(&self, pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+        assert_eq!(
+            pairs.len(),
+            output.len(),
+            "input and output batches must be the same length"
+        );
+        for (pair, out) in pairs.iter().zip(output.iter_mut()) {
+            *out = self.hash_64(pair);
+        }
+    }
 }
 
 /// Implementation of SHA256 using the `ring` crate (fastest on CPUs without SHA extensions).
+#[cfg(feature = "ring")]
 pub struct RingImpl;
 
+#[cfg(feature = "ring")]
 impl Sha256Context for ring::digest::Context {
     fn new() -> Self {
         Self::new(&ring::digest::SHA256)
@@ -72,11 +278,16 @@ impl Sha256Context for ring::digest::Context {
 
     fn finalize(self) -> [u8; HASH_LEN] {
         let mut output = [0; HASH_LEN];
-        output.copy_from_slice(self.finish().as_ref());
+        self.finalize_into(&mut output);
         output
     }
+
+    fn finalize_into(self, out: &mut [u8; HASH_LEN]) {
+        out.copy_from_slice(self.finish().as_ref());
+    }
 }
 
+#[cfg(feature = "ring")]
 impl Sha256 for RingImpl {
     type Context = ring::digest::Context;
 
@@ -91,13 +302,24 @@ impl Sha256 for RingImpl {
         ctxt.update(input);
         ctxt.finalize()
     }
+
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        out.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, input).as_ref());
+    }
 }
 
 /// Default dynamic implementation that switches between available implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DynamicImpl {
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
     Sha2,
+    #[cfg(target_arch = "x86_64")]
+    Avx512,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(feature = "ring")]
     Ring,
+    Portable,
 }
 
 // Runtime latch for detecting the availability of SHA extensions on x86_64.
@@ -106,28 +328,86 @@ pub enum DynamicImpl {
 #[cfg(target_arch = "x86_64")]
 cpufeatures::new!(x86_sha_extensions, "sha", "sse2", "ssse3", "sse4.1");
 
+// Runtime latch for detecting the availability of the SHA2 crypto extensions on aarch64.
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(aarch64_sha_extensions, "sha2");
+
+// Runtime latches for the vector extensions used by the multi-buffer implementations.
+#[cfg(target_arch = "x86_64")]
+cpufeatures::new!(x86_avx2, "avx2");
+#[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+cpufeatures::new!(x86_avx512, "avx512f", "avx2");
+
 #[inline(always)]
 pub fn have_sha_extensions() -> bool {
     #[cfg(target_arch = "x86_64")]
     return x86_sha_extensions::get();
 
+    #[cfg(target_arch = "aarch64")]
+    return aarch64_sha_extensions::get();
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    return false;
+}
+
+#[inline(always)]
+pub fn have_avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    return x86_avx2::get();
+
     #[cfg(not(target_arch = "x86_64"))]
     return false;
 }
 
+#[inline(always)]
+pub fn have_avx512() -> bool {
+    #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+    return x86_avx512::get();
+
+    #[cfg(not(all(target_arch = "x86_64", feature = "avx512")))]
+    return false;
+}
+
 impl DynamicImpl {
-    /// Choose the best available implementation based on the currently executing CPU.
+    /// Choose the implementation to use.
+    ///
+    /// This is the backend pinned by `Backend::force` or `BACKEND_ENV_VAR` if there is one, and
+    /// otherwise the result of `detect`.
     #[inline(always)]
     pub fn best() -> Self {
+        backend::forced().unwrap_or_else(Self::detect)
+    }
+
+    /// Choose the best available implementation based on the currently executing CPU.
+    ///
+    /// Without SHA intrinsics or AVX, `ring` is preferred, followed by `sha2` (which falls back to
+    /// software) and the portable implementation. Backends disabled by `self_test` are skipped,
+    /// except for `Portable`, which is the last resort.
+    #[inline(always)]
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        if have_sha_extensions() && !Backend::Sha2.is_disabled() {
+            return Self::Sha2;
+        }
+
         #[cfg(target_arch = "x86_64")]
-        if have_sha_extensions() {
-            Self::Sha2
-        } else {
-            Self::Ring
+        if have_avx512() && !Backend::Avx512.is_disabled() {
+            return Self::Avx512;
+        } else if have_avx2() && !Backend::Avx2.is_disabled() {
+            return Self::Avx2;
+        }
+
+        #[cfg(feature = "ring")]
+        if !Backend::Ring.is_disabled() {
+            return Self::Ring;
+        }
+
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        if !Backend::Sha2.is_disabled() {
+            return Self::Sha2;
         }
 
-        #[cfg(not(target_arch = "x86_64"))]
-        Self::Ring
+        Self::Portable
     }
 }
 
@@ -137,18 +417,75 @@ impl Sha256 for DynamicImpl {
     #[inline(always)]
     fn hash(&self, input: &[u8]) -> Vec<u8> {
         match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Sha2Impl.hash(input),
             #[cfg(target_arch = "x86_64")]
-            Self::Sha2 => Sha2CrateImpl.hash(input),
+            Self::Avx512 => Avx512Impl.hash(input),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => Avx2Impl.hash(input),
+            #[cfg(feature = "ring")]
             Self::Ring => RingImpl.hash(input),
+            Self::Portable => PortableImpl.hash(input),
         }
     }
 
     #[inline(always)]
     fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
         match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Sha2Impl.hash_fixed(input),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => Avx512Impl.hash_fixed(input),
             #[cfg(target_arch = "x86_64")]
-            Self::Sha2 => Sha2CrateImpl.hash_fixed(input),
+            Self::Avx2 => Avx2Impl.hash_fixed(input),
+            #[cfg(feature = "ring")]
             Self::Ring => RingImpl.hash_fixed(input),
+            Self::Portable => PortableImpl.hash_fixed(input),
+        }
+    }
+
+    #[inline(always)]
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Sha2Impl.hash_into(input, out),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => Avx512Impl.hash_into(input, out),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => Avx2Impl.hash_into(input, out),
+            #[cfg(feature = "ring")]
+            Self::Ring => RingImpl.hash_into(input, out),
+            Self::Portable => PortableImpl.hash_into(input, out),
+        }
+    }
+
+    #[inline(always)]
+    fn hash_64(&self, input: &[u8; 64]) -> [u8; HASH_LEN] {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Sha2Impl.hash_64(input),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => Avx512Impl.hash_64(input),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => Avx2Impl.hash_64(input),
+            #[cfg(feature = "ring")]
+            Self::Ring => RingImpl.hash_64(input),
+            Self::Portable => PortableImpl.hash_64(input),
+        }
+    }
+
+    #[inline(always)]
+    fn hash32_concat_batch(&self, pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2 => Sha2Impl.hash32_concat_batch(pairs, output),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => Avx512Impl.hash32_concat_batch(pairs, output),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => Avx2Impl.hash32_concat_batch(pairs, output),
+            #[cfg(feature = "ring")]
+            Self::Ring => RingImpl.hash32_concat_batch(pairs, output),
+            Self::Portable => PortableImpl.hash32_concat_batch(pairs, output),
         }
     }
 }
@@ -156,34 +493,80 @@ impl Sha256 for DynamicImpl {
 /// Context encapsulating all implemenation contexts.
 ///
 /// This enum ends up being 8 bytes larger than the largest inner context.
+#[derive(Clone)]
 pub enum DynamicContext {
-    #[cfg(target_arch = "x86_64")]
-    Sha2(sha2::Sha256),
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    Sha2(<Sha2Impl as Sha256>::Context),
+    #[cfg(feature = "ring")]
     Ring(ring::digest::Context),
+    Portable(PortableContext),
 }
 
 impl Sha256Context for DynamicContext {
     fn new() -> Self {
         match DynamicImpl::best() {
-            #[cfg(target_arch = "x86_64")]
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
             DynamicImpl::Sha2 => Self::Sha2(Sha256Context::new()),
+            // The multi-buffer implementations use `ring` for single messages, or `sha2` without
+            // the `ring` feature.
+            #[cfg(all(target_arch = "x86_64", feature = "ring"))]
+            DynamicImpl::Avx512 | DynamicImpl::Avx2 => Self::Ring(Sha256Context::new()),
+            #[cfg(all(target_arch = "x86_64", not(feature = "ring")))]
+            DynamicImpl::Avx512 | DynamicImpl::Avx2 => Self::Sha2(Sha256Context::new()),
+            #[cfg(feature = "ring")]
             DynamicImpl::Ring => Self::Ring(Sha256Context::new()),
+            DynamicImpl::Portable => Self::Portable(Sha256Context::new()),
         }
     }
 
     fn update(&mut self, bytes: &[u8]) {
         match self {
-            #[cfg(target_arch = "x86_64")]
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
             Self::Sha2(ctxt) => Sha256Context::update(ctxt, bytes),
+            #[cfg(feature = "ring")]
             Self::Ring(ctxt) => Sha256Context::update(ctxt, bytes),
+            Self::Portable(ctxt) => Sha256Context::update(ctxt, bytes),
         }
     }
 
     fn finalize(self) -> [u8; HASH_LEN] {
         match self {
-            #[cfg(target_arch = "x86_64")]
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
             Self::Sha2(ctxt) => Sha256Context::finalize(ctxt),
+            #[cfg(feature = "ring")]
             Self::Ring(ctxt) => Sha256Context::finalize(ctxt),
+            Self::Portable(ctxt) => Sha256Context::finalize(ctxt),
+        }
+    }
+
+    fn finalize_into(self, out: &mut [u8; HASH_LEN]) {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2(ctxt) => Sha256Context::finalize_into(ctxt, out),
+            #[cfg(feature = "ring")]
+            Self::Ring(ctxt) => Sha256Context::finalize_into(ctxt, out),
+            Self::Portable(ctxt) => Sha256Context::finalize_into(ctxt, out),
+        }
+    }
+
+    // Resetting keeps the implementation the context was created with.
+    fn reset(&mut self) {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2(ctxt) => Sha256Context::reset(ctxt),
+            #[cfg(feature = "ring")]
+            Self::Ring(ctxt) => Sha256Context::reset(ctxt),
+            Self::Portable(ctxt) => Sha256Context::reset(ctxt),
+        }
+    }
+
+    fn finalize_reset(&mut self) -> [u8; HASH_LEN] {
+        match self {
+            #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+            Self::Sha2(ctxt) => Sha256Context::finalize_reset(ctxt),
+            #[cfg(feature = "ring")]
+            Self::Ring(ctxt) => Sha256Context::finalize_reset(ctxt),
+            Self::Portable(ctxt) => Sha256Context::finalize_reset(ctxt),
         }
     }
 }
@@ -192,17 +575,80 @@ impl Sha256Context for DynamicContext {
 #[cfg(feature = "zero_hash_cache")]
 pub const ZERO_HASHES_MAX_INDEX: usize = 48;
 
+/// The deepest tree whose root `zero_hash` will compute, which bounds the memory used to memoize
+/// the roots beyond `ZERO_HASHES`. A tree of `2^64` chunks only needs depth 64.
+#[cfg(feature = "zero_hash_cache")]
+pub const ZERO_HASH_MAX_DEPTH: usize = 128;
+
 #[cfg(feature = "zero_hash_cache")]
 /// Cached zero hashes where `ZERO_HASHES[i]` is the hash of a Merkle tree with 2^i zero leaves.
-pub static ZERO_HASHES: LazyLock<Vec<[u8; HASH_LEN]>> = LazyLock::new(|| {
-    let mut hashes = vec![[0; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1];
+///
+/// The table is computed at compile time.
+pub static ZERO_HASHES: [[u8; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1] = zero_hashes();
 
-    for i in 0..ZERO_HASHES_MAX_INDEX {
-        hashes[i + 1] = hash32_concat(&hashes[i], &hashes[i]);
+/// The root of a Merkle tree with 2^`depth` zero leaves.
+///
+/// Depths up to `ZERO_HASHES_MAX_INDEX` are read from `ZERO_HASHES`. Deeper roots are computed
+/// from the last cached one, and with the `std` feature are memoized so that each is only hashed
+/// once.
+///
+/// # Panics
+///
+/// Panics if `depth` exceeds `ZERO_HASH_MAX_DEPTH`.
+#[cfg(feature = "zero_hash_cache")]
+pub fn zero_hash(depth: usize) -> [u8; HASH_LEN] {
+    assert!(
+        depth <= ZERO_HASH_MAX_DEPTH,
+        "zero hash depth {depth} exceeds {ZERO_HASH_MAX_DEPTH}"
+    );
+    match ZERO_HASHES.get(depth) {
+        Some(hash) => *hash,
+        None => deep_zero_hash(depth),
     }
+}
 
+/// Compute a zero hash deeper than `ZERO_HASHES_MAX_INDEX`, memoizing every level up to `depth`.
+#[cfg(all(feature = "zero_hash_cache", feature = "std"))]
+fn deep_zero_hash(depth: usize) -> [u8; HASH_LEN] {
+    use std::sync::{Mutex, PoisonError};
+
+    /// Zero hashes from depth `ZERO_HASHES_MAX_INDEX + 1` onwards.
+    static DEEP_ZERO_HASHES: Mutex<Vec<[u8; HASH_LEN]>> = Mutex::new(Vec::new());
+
+    let index = depth - ZERO_HASHES_MAX_INDEX - 1;
+    // The hashes are pushed one at a time, so a panic while holding the lock can't leave them
+    // inconsistent.
+    let mut hashes = DEEP_ZERO_HASHES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    while hashes.len() <= index {
+        let child = hashes.last().unwrap_or(&ZERO_HASHES[ZERO_HASHES_MAX_INDEX]);
+        let parent = hash32_concat(child, child);
+        hashes.push(parent);
+    }
+    hashes[index]
+}
+
+/// Compute a zero hash deeper than `ZERO_HASHES_MAX_INDEX`.
+#[cfg(all(feature = "zero_hash_cache", not(feature = "std")))]
+fn deep_zero_hash(depth: usize) -> [u8; HASH_LEN] {
+    let mut hash = ZERO_HASHES[ZERO_HASHES_MAX_INDEX];
+    for _ in ZERO_HASHES_MAX_INDEX..depth {
+        hash = hash32_concat(&hash, &hash);
+    }
+    hash
+}
+
+#[cfg(feature = "zero_hash_cache")]
+const fn zero_hashes() -> [[u8; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1] {
+    let mut hashes = [[0; HASH_LEN]; ZERO_HASHES_MAX_INDEX + 1];
+    let mut i = 0;
+    while i < ZERO_HASHES_MAX_INDEX {
+        hashes[i + 1] = hash32_concat_const(&hashes[i], &hashes[i]);
+        i += 1;
+    }
     hashes
-});
+}
 
 #[cfg(test)]
 mod tests {
@@ -223,6 +669,155 @@ mod tests {
         assert_eq!(expected, output);
     }
 
+    /// Run `check` on every backend that is compiled in and supported by the CPU.
+    fn for_each_backend(check: impl Fn(DynamicImpl)) {
+        Backend::ALL
+            .into_iter()
+            .filter(|backend| backend.is_supported())
+            .filter_map(Backend::dynamic_impl)
+            .for_each(check);
+    }
+
+    #[test]
+    fn hash_into_matches_hash_fixed() {
+        fn check<H: Sha256>(implementation: H) {
+            for input in [&b""[..], b"abc", &[7; 200]] {
+                let mut out = [0; HASH_LEN];
+                implementation.hash_into(input, &mut out);
+                assert_eq!(out, implementation.hash_fixed(input));
+
+                let mut ctxt = H::Context::new();
+                ctxt.update(input);
+                let mut out = [0; HASH_LEN];
+                ctxt.finalize_into(&mut out);
+                assert_eq!(out, implementation.hash_fixed(input));
+            }
+        }
+
+        for_each_backend(check);
+
+        let mut out = [0; HASH_LEN];
+        hash_into(b"abc", &mut out);
+        assert_eq!(out, hash_fixed(b"abc"));
+    }
+
+    #[test]
+    fn fork_and_reset_contexts() {
+        fn check<C: Sha256Context>() {
+            let mut prefix = C::new();
+            prefix.update(b"domain");
+            for suffix in [&b""[..], b"abc", &[7; 100]] {
+                let mut ctxt = prefix.clone();
+                ctxt.update(suffix);
+                assert_eq!(ctxt.finalize(), hash_fixed(&[b"domain", suffix].concat()));
+            }
+
+            let mut ctxt = prefix.clone();
+            ctxt.update(b"abc");
+            assert_eq!(ctxt.finalize_reset(), hash_fixed(b"domainabc"));
+            ctxt.update(b"abc");
+            assert_eq!(ctxt.finalize_reset(), hash_fixed(b"abc"));
+
+            ctxt.update(b"discarded");
+            ctxt.reset();
+            ctxt.update(b"abc");
+            assert_eq!(ctxt.finalize(), hash_fixed(b"abc"));
+        }
+
+        check::<DynamicContext>();
+        #[cfg(feature = "ring")]
+        check::<ring::digest::Context>();
+        check::<PortableContext>();
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        check::<<Sha2Impl as Sha256>::Context>();
+    }
+
+    #[test]
+    fn hash_64_matches_hash_fixed() {
+        fn check<H: Sha256>(implementation: H) {
+            for i in 0..4u8 {
+                let input = [i; 64];
+                assert_eq!(
+                    implementation.hash_64(&input),
+                    PortableImpl.hash_fixed(&input)
+                );
+            }
+        }
+
+        for_each_backend(check);
+
+        let (h1, h2) = ([1; 32], [2; 32]);
+        assert_eq!(hash32_concat(&h1, &h2), hash_fixed(&[h1, h2].concat()));
+        assert_eq!(
+            hash32_concat(&h1, &h2[1..]),
+            hash_fixed(&[&h1, &h2[1..]].concat())
+        );
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn detect_uses_sha2_extensions() {
+        if std::arch::is_aarch64_feature_detected!("sha2") {
+            assert!(have_sha_extensions());
+            assert_eq!(DynamicImpl::detect(), DynamicImpl::Sha2);
+        }
+    }
+
+    #[test]
+    fn hash256_variants() {
+        assert_eq!(hash256(b"abc").0, hash_fixed(b"abc"));
+        assert_eq!(hash256_concat(b"ab", b"c"), hash256(b"abc"));
+
+        let mut ctxt = DynamicContext::new();
+        ctxt.update(b"abc");
+        assert_eq!(ctxt.finalize_hash256(), hash256(b"abc"));
+    }
+
+    #[test]
+    fn hash32_concat_batch_matches_hash32_concat() {
+        let pairs: Vec<[u8; 64]> = (0..19u8).map(|i| [i; 64]).collect();
+        let mut output = vec![[0; HASH_LEN]; pairs.len()];
+        hash32_concat_batch(&pairs, &mut output);
+
+        for (pair, digest) in pairs.iter().zip(&output) {
+            assert_eq!(*digest, hash32_concat(&pair[..32], &pair[32..]));
+        }
+    }
+
+    #[test]
+    fn mix_ins() {
+        let root = hash_fixed(b"root");
+        let mut expected_input = [0; 64];
+        expected_input[..32].copy_from_slice(&root);
+
+        expected_input[32..36].copy_from_slice(&0x0506_0708u32.to_le_bytes());
+        assert_eq!(
+            mix_in_length(&root, 0x0506_0708),
+            hash_fixed(&expected_input)
+        );
+
+        expected_input[32..40].copy_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+        assert_eq!(
+            mix_in_length_u64(&root, 0x0102_0304_0506_0708),
+            hash_fixed(&expected_input)
+        );
+        assert_eq!(length_chunk(0x0102_0304_0506_0708), expected_input[32..]);
+
+        expected_input[32..].fill(0);
+        expected_input[32] = 5;
+        assert_eq!(mix_in_selector(&root, 5), hash_fixed(&expected_input));
+
+        let aux = hash_fixed(b"aux");
+        expected_input[32..].copy_from_slice(&aux);
+        assert_eq!(mix_in_aux(&root, &aux), hash_fixed(&expected_input));
+    }
+
+    #[test]
+    #[should_panic]
+    fn hash32_concat_batch_length_mismatch() {
+        hash32_concat_batch(&[[0; 64]; 2], &mut [[0; HASH_LEN]; 1]);
+    }
+
     #[cfg(feature = "zero_hash_cache")]
     mod zero_hash {
         use super::*;
@@ -231,5 +826,44 @@ mod tests {
         fn zero_hash_zero() {
             assert_eq!(ZERO_HASHES[0], [0; 32]);
         }
+
+        #[test]
+        fn zero_hashes_match_runtime_hashing() {
+            for i in 0..ZERO_HASHES_MAX_INDEX {
+                assert_eq!(
+                    ZERO_HASHES[i + 1],
+                    hash32_concat(&ZERO_HASHES[i], &ZERO_HASHES[i])
+                );
+            }
+        }
+
+        #[test]
+        fn zero_hash_beyond_cache() {
+            for (depth, expected) in ZERO_HASHES.iter().enumerate() {
+                assert_eq!(zero_hash(depth), *expected);
+            }
+
+            let mut expected = ZERO_HASHES[ZERO_HASHES_MAX_INDEX];
+            for depth in ZERO_HASHES_MAX_INDEX + 1..=80 {
+                expected = hash32_concat(&expected, &expected);
+                assert_eq!(zero_hash(depth), expected, "{depth}");
+            }
+            // Memoized levels are served again, in any order.
+            assert_eq!(zero_hash(80), expected);
+            assert_eq!(zero_hash(60), hash32_concat(&zero_hash(59), &zero_hash(59)));
+            assert_eq!(
+                zero_hash(ZERO_HASH_MAX_DEPTH),
+                hash32_concat(
+                    &zero_hash(ZERO_HASH_MAX_DEPTH - 1),
+                    &zero_hash(ZERO_HASH_MAX_DEPTH - 1)
+                )
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "exceeds")]
+        fn zero_hash_too_deep() {
+            zero_hash(ZERO_HASH_MAX_DEPTH + 1);
+        }
     }
 }
@@ -0,0 +1,539 @@
+//! Merkleization of 32-byte chunks, as defined by the SSZ specification.
+use crate::{
+    hash32_concat, hash32_concat_batch, zero_hash, GeneralizedIndex, Merkleizer, HASH_LEN,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Compute the Merkle root of `chunks`, padded with zero chunks to `limit` leaves.
+///
+/// If `limit` is `None` the tree is padded to the next power of two of `chunks.len()`. Padding is
+/// never hashed: zero subtrees are taken from `zero_hash`.
+///
+/// This matches the `merkleize` function from the SSZ specification.
+///
+/// # Panics
+///
+/// Panics if `chunks.len()` exceeds `limit`.
+pub fn merkleize(chunks: &[[u8; HASH_LEN]], limit: Option<usize>) -> [u8; HASH_LEN] {
+    let depth = limit_depth(chunks.len(), limit);
+
+    let Some((first, _)) = chunks.split_first() else {
+        return zero_hash(depth);
+    };
+    if chunks.len() == 1 {
+        return zero_pad_root(*first, 0, depth);
+    }
+
+    merkleize_upper_levels(merkleize_level(chunks, 0), 1, depth)
+}
+
+/// Compute the same root as `merkleize`, hashing subtrees in parallel on the `rayon` thread pool.
+///
+/// The chunks are split into equal power-of-two subtrees, one or more per thread, whose roots are
+/// then combined. Small inputs are hashed on the calling thread.
+///
+/// # Panics
+///
+/// Panics if `chunks.len()` exceeds `limit`.
+#[cfg(feature = "rayon")]
+pub fn merkleize_parallel(chunks: &[[u8; HASH_LEN]], limit: Option<usize>) -> [u8; HASH_LEN] {
+    use rayon::prelude::*;
+
+    /// Minimum depth of the subtrees hashed by each task.
+    const MIN_SUBTREE_DEPTH: usize = 10;
+
+    let depth = limit_depth(chunks.len(), limit);
+    let subtree_depth =
+        tree_depth(chunks.len().div_ceil(rayon::current_num_threads())).max(MIN_SUBTREE_DEPTH);
+    if chunks.len() <= 1 << subtree_depth {
+        return merkleize(chunks, limit);
+    }
+
+    let subtree_leaves = 1 << subtree_depth;
+    let subtree_roots = chunks
+        .par_chunks(subtree_leaves)
+        .map(|subtree| merkleize(subtree, Some(subtree_leaves)))
+        .collect();
+
+    merkleize_upper_levels(subtree_roots, subtree_depth, depth)
+}
+
+/// Compute the Merkle root of the chunks yielded by `chunks`, exactly like `merkleize`.
+///
+/// Chunks are buffered in fixed blocks, each hashed as a subtree with batched hashing, and the
+/// subtree roots are merged into one pending node per level, so memory use is bounded by the
+/// depth of the tree.
+pub(crate) fn merkleize_chunks<I>(mut chunks: I, limit: Option<usize>) -> [u8; HASH_LEN]
+where
+    I: ExactSizeIterator<Item = [u8; HASH_LEN]>,
+{
+    const SUBTREE_DEPTH: usize = 8;
+    const SUBTREE_LEAVES: usize = 1 << SUBTREE_DEPTH;
+
+    let depth = limit_depth(chunks.len(), limit);
+    let mut block = [[0; HASH_LEN]; SUBTREE_LEAVES];
+    let mut fill_block = |block: &mut [[u8; HASH_LEN]; SUBTREE_LEAVES]| {
+        block
+            .iter_mut()
+            .zip(&mut chunks)
+            .map(|(slot, chunk)| *slot = chunk)
+            .count()
+    };
+
+    // A tree that fits in one block is hashed directly.
+    if depth <= SUBTREE_DEPTH {
+        let filled = fill_block(&mut block);
+        return merkleize(&block[..filled], Some(1 << depth));
+    }
+
+    let mut merkleizer = Merkleizer::with_leaf_level(1 << (depth - SUBTREE_DEPTH), SUBTREE_DEPTH);
+    loop {
+        let filled = fill_block(&mut block);
+        if filled == 0 {
+            break;
+        }
+        merkleizer
+            .write(merkleize(&block[..filled], Some(SUBTREE_LEAVES)))
+            .expect("the chunks fit in the tree");
+        if filled < SUBTREE_LEAVES {
+            break;
+        }
+    }
+
+    merkleizer.finish()
+}
+
+/// Check `count` chunks against `limit`, returning the depth of the padded tree.
+fn limit_depth(count: usize, limit: Option<usize>) -> usize {
+    let leaves = match limit {
+        Some(limit) => {
+            assert!(
+                count <= limit,
+                "number of chunks ({count}) exceeds limit ({limit})"
+            );
+            limit
+        }
+        None => count,
+    };
+    tree_depth(leaves)
+}
+
+/// Compute the root of a tree of the given `depth` from its non-empty nodes at `level`.
+fn merkleize_upper_levels(
+    mut nodes: Vec<[u8; HASH_LEN]>,
+    mut level: usize,
+    depth: usize,
+) -> [u8; HASH_LEN] {
+    while nodes.len() > 1 {
+        nodes = merkleize_level(&nodes, level);
+        level += 1;
+    }
+
+    zero_pad_root(nodes[0], level, depth)
+}
+
+/// Compute the Merkle branch for the leaf at `index` in a tree of the given `depth`.
+///
+/// The tree's leaves are `leaves`, padded with zero chunks to `2^depth` leaves. The branch lists
+/// the sibling of each node on the path from the leaf to the root, starting at the leaf's sibling.
+/// Siblings that lie entirely in the padding are taken from `zero_hash`.
+///
+/// # Panics
+///
+/// Panics if `leaves` or `index` don't fit in a tree of the given `depth`, or if `depth` exceeds
+/// `ZERO_HASH_MAX_DEPTH + 1`.
+pub fn merkle_branch(leaves: &[[u8; HASH_LEN]], depth: usize, index: usize) -> Vec<[u8; HASH_LEN]> {
+    assert!(
+        tree_depth(leaves.len()) <= depth && tree_depth(index + 1) <= depth,
+        "leaves ({}) or index ({index}) out of range for depth {depth}",
+        leaves.len()
+    );
+
+    let mut branch = Vec::with_capacity(depth);
+    let mut nodes = leaves.to_vec();
+    let mut index = index;
+    for level in 0..depth {
+        branch.push(
+            nodes
+                .get(index ^ 1)
+                .copied()
+                .unwrap_or_else(|| zero_hash(level)),
+        );
+        if !nodes.is_empty() {
+            nodes = merkleize_level(&nodes, level);
+        }
+        index >>= 1;
+    }
+
+    branch
+}
+
+/// Compute the root of a Merkle tree from a leaf at `index` and its `branch`.
+///
+/// The depth of the tree is the length of the branch.
+pub fn merkle_root_from_branch(
+    leaf: &[u8; HASH_LEN],
+    branch: &[[u8; HASH_LEN]],
+    index: u64,
+) -> [u8; HASH_LEN] {
+    let mut value = *leaf;
+    for (level, sibling) in branch.iter().enumerate() {
+        // Levels past the 64 bits of `index` are left turns.
+        if level < 64 && (index >> level) & 1 == 1 {
+            value = hash32_concat(sibling, &value);
+        } else {
+            value = hash32_concat(&value, sibling);
+        }
+    }
+    value
+}
+
+/// Check that `leaf` at `index` is included in the tree with the given `root` and `depth`.
+///
+/// Returns `false` if `branch` is shorter than `depth`. Extra branch entries are ignored.
+///
+/// This matches the `is_valid_merkle_branch` function from the consensus specification.
+pub fn is_valid_merkle_branch(
+    leaf: &[u8; HASH_LEN],
+    branch: &[[u8; HASH_LEN]],
+    depth: usize,
+    index: u64,
+    root: &[u8; HASH_LEN],
+) -> bool {
+    branch
+        .get(..depth)
+        .is_some_and(|branch| merkle_root_from_branch(leaf, branch, index) == *root)
+}
+
+/// Check that `leaf` is included at generalized index `gindex` in the tree with the given `root`.
+///
+/// The depth and leaf index are derived from `gindex`. Any branch entries beyond the depth of
+/// `gindex` must come first and be zero, which allows a branch to stay valid when the tree it
+/// proves against grows deeper.
+///
+/// This matches the `is_valid_normalized_merkle_branch` function from the consensus
+/// specification.
+pub fn is_valid_normalized_merkle_branch(
+    leaf: &[u8; HASH_LEN],
+    branch: &[[u8; HASH_LEN]],
+    gindex: GeneralizedIndex,
+    root: &[u8; HASH_LEN],
+) -> bool {
+    let depth = gindex.depth() as usize;
+    let Some(num_extra) = branch.len().checked_sub(depth) else {
+        return false;
+    };
+    let (extra, branch) = branch.split_at(num_extra);
+
+    extra.iter().all(|node| *node == [0; HASH_LEN])
+        && is_valid_merkle_branch(leaf, branch, depth, gindex.subtree_index(), root)
+}
+
+/// Depth of the smallest tree with at least `leaves` leaves.
+pub(crate) fn tree_depth(leaves: usize) -> usize {
+    if leaves <= 1 {
+        0
+    } else {
+        (usize::BITS - (leaves - 1).leading_zeros()) as usize
+    }
+}
+
+/// Hash `nodes` pairwise to produce the next level up, padding an odd node with the zero hash at
+/// `level`.
+fn merkleize_level(nodes: &[[u8; HASH_LEN]], level: usize) -> Vec<[u8; HASH_LEN]> {
+    // SAFETY: two adjacent `[u8; HASH_LEN]` nodes have the same layout as `[u8; 64]`.
+    let pairs: &[[u8; 64]] =
+        unsafe { core::slice::from_raw_parts(nodes.as_ptr().cast(), nodes.len() / 2) };
+
+    let mut parents = vec![[0; HASH_LEN]; nodes.len().div_ceil(2)];
+    hash32_concat_batch(pairs, &mut parents[..pairs.len()]);
+    if nodes.len() % 2 == 1 {
+        parents[pairs.len()] = hash32_concat(&nodes[nodes.len() - 1], &zero_hash(level));
+    }
+
+    parents
+}
+
+/// Hash `root`, the root of a subtree at `level`, with zero subtrees until it reaches `depth`.
+fn zero_pad_root(mut root: [u8; HASH_LEN], level: usize, depth: usize) -> [u8; HASH_LEN] {
+    for level in level..depth {
+        root = hash32_concat(&root, &zero_hash(level));
+    }
+    root
+}
+
+/// Distinct chunks for tests: chunk `i` is filled with the byte `i + 1`.
+#[cfg(test)]
+pub(crate) fn test_chunks(count: usize) -> Vec<[u8; HASH_LEN]> {
+    (1..=count).map(|i| [i as u8; HASH_LEN]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZERO_HASHES;
+
+    /// Unoptimized Merkleization that hashes the padding explicitly.
+    fn reference_merkleize(chunks: &[[u8; HASH_LEN]], limit: Option<usize>) -> [u8; HASH_LEN] {
+        let leaves = limit.unwrap_or(chunks.len()).next_power_of_two();
+        let mut nodes = chunks.to_vec();
+        nodes.resize(leaves, [0; HASH_LEN]);
+        while nodes.len() > 1 {
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| hash32_concat(&pair[0], &pair[1]))
+                .collect();
+        }
+        nodes[0]
+    }
+
+    #[test]
+    fn tree_depths() {
+        assert_eq!(tree_depth(0), 0);
+        assert_eq!(tree_depth(1), 0);
+        assert_eq!(tree_depth(2), 1);
+        assert_eq!(tree_depth(3), 2);
+        assert_eq!(tree_depth(4), 2);
+        assert_eq!(tree_depth(5), 3);
+        assert_eq!(tree_depth(1 << 40), 40);
+        assert_eq!(tree_depth((1 << 40) + 1), 41);
+        assert_eq!(tree_depth(usize::MAX), usize::BITS as usize);
+    }
+
+    #[test]
+    fn odd_level() {
+        for count in [1, 3, 5, 17] {
+            let nodes = test_chunks(count);
+            let parents = merkleize_level(&nodes, 2);
+            assert_eq!(parents.len(), count.div_ceil(2));
+            for (i, parent) in parents.iter().enumerate() {
+                let right = nodes.get(2 * i + 1).unwrap_or(&ZERO_HASHES[2]);
+                assert_eq!(*parent, hash32_concat(&nodes[2 * i], right), "{count} {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(merkleize(&[], None), [0; HASH_LEN]);
+        assert_eq!(merkleize(&[], Some(0)), [0; HASH_LEN]);
+        assert_eq!(merkleize(&[], Some(5)), ZERO_HASHES[3]);
+        assert_eq!(merkleize(&[], Some(1 << 40)), ZERO_HASHES[40]);
+    }
+
+    #[test]
+    fn single_chunk() {
+        let chunk = [0xab; HASH_LEN];
+        assert_eq!(merkleize(&[chunk], None), chunk);
+        assert_eq!(merkleize(&[chunk], Some(1)), chunk);
+        assert_eq!(
+            merkleize(&[chunk], Some(2)),
+            hash32_concat(&chunk, &[0; HASH_LEN])
+        );
+    }
+
+    #[test]
+    fn matches_reference() {
+        for count in 0..=33 {
+            let chunks = test_chunks(count);
+            assert_eq!(
+                merkleize(&chunks, None),
+                reference_merkleize(&chunks, None),
+                "count {count}"
+            );
+            for limit in [count, count + 1, 64] {
+                assert_eq!(
+                    merkleize(&chunks, Some(limit)),
+                    reference_merkleize(&chunks, Some(limit)),
+                    "count {count} limit {limit}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_merkleize() {
+        for count in [0, 1, 2, 3, 31, 32, 33, 64, 100, 255, 256, 257, 512, 1000] {
+            let chunks = test_chunks(count);
+            for limit in [None, Some(count), Some(1 << 20), Some(usize::MAX)] {
+                assert_eq!(
+                    merkleize_chunks(chunks.iter().copied(), limit),
+                    merkleize(&chunks, limit),
+                    "count {count} limit {limit:?}"
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_merkleize() {
+        let large = (0..5000u32)
+            .map(|i| crate::hash_fixed(&i.to_le_bytes()))
+            .collect::<Vec<_>>();
+
+        // Use several threads regardless of the host, so that the chunks are actually split.
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            for count in [0, 1, 1000, 1024, 1025, 2048, 4097, 5000] {
+                let chunks = &large[..count];
+                for limit in [None, Some(count), Some(1 << 40)] {
+                    assert_eq!(
+                        merkleize_parallel(chunks, limit),
+                        merkleize(chunks, limit),
+                        "count {count} limit {limit:?}"
+                    );
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn large_limit() {
+        let chunks = test_chunks(5);
+        let mut expected = reference_merkleize(&chunks, None);
+        for zero_hash in &ZERO_HASHES[3..40] {
+            expected = hash32_concat(&expected, zero_hash);
+        }
+        assert_eq!(merkleize(&chunks, Some(1 << 40)), expected);
+    }
+
+    #[test]
+    fn deeper_than_zero_hash_cache() {
+        let chunks = test_chunks(5);
+        let mut expected = reference_merkleize(&chunks, None);
+        for level in 3..usize::BITS as usize {
+            expected = hash32_concat(&expected, &zero_hash(level));
+        }
+        assert_eq!(merkleize(&chunks, Some(usize::MAX)), expected);
+        assert_eq!(
+            merkleize(&[], Some(usize::MAX)),
+            zero_hash(usize::BITS as usize)
+        );
+
+        let depth = 60;
+        let root = merkleize(&chunks, Some(1 << depth));
+        let branch = merkle_branch(&chunks, depth, 3);
+        assert_eq!(branch[59], zero_hash(59));
+        assert!(is_valid_merkle_branch(&chunks[3], &branch, depth, 3, &root));
+    }
+
+    #[test]
+    fn branches_are_valid() {
+        let depth = 4;
+        for count in [0, 1, 2, 5, 8, 16] {
+            let leaves = test_chunks(count);
+            let root = merkleize(&leaves, Some(1 << depth));
+
+            for index in 0..1 << depth {
+                let leaf = leaves.get(index).copied().unwrap_or([0; HASH_LEN]);
+                let branch = merkle_branch(&leaves, depth, index);
+                assert_eq!(branch.len(), depth);
+                assert!(is_valid_merkle_branch(
+                    &leaf,
+                    &branch,
+                    depth,
+                    index as u64,
+                    &root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_branches() {
+        let depth = 3;
+        let leaves = test_chunks(6);
+        let root = merkleize(&leaves, Some(1 << depth));
+        let mut branch = merkle_branch(&leaves, depth, 2);
+
+        assert!(is_valid_merkle_branch(&leaves[2], &branch, depth, 2, &root));
+        assert!(!is_valid_merkle_branch(
+            &leaves[3], &branch, depth, 2, &root
+        ));
+        assert!(!is_valid_merkle_branch(
+            &leaves[2],
+            &branch[..2],
+            depth,
+            2,
+            &root
+        ));
+
+        branch[1][0] ^= 1;
+        assert!(!is_valid_merkle_branch(
+            &leaves[2], &branch, depth, 2, &root
+        ));
+    }
+
+    #[test]
+    fn normalized_branches() {
+        let depth = 3;
+        let leaves = test_chunks(8);
+        let root = merkleize(&leaves, Some(1 << depth));
+        let gindex = GeneralizedIndex::from_depth_and_index(depth as u32, 5).unwrap();
+        let branch = merkle_branch(&leaves, depth, 5);
+
+        assert!(is_valid_normalized_merkle_branch(
+            &leaves[5], &branch, gindex, &root
+        ));
+
+        let mut padded = vec![[0; HASH_LEN]; 2];
+        padded.extend_from_slice(&branch);
+        assert!(is_valid_normalized_merkle_branch(
+            &leaves[5], &padded, gindex, &root
+        ));
+
+        padded[0][0] = 1;
+        assert!(!is_valid_normalized_merkle_branch(
+            &leaves[5], &padded, gindex, &root
+        ));
+        assert!(!is_valid_normalized_merkle_branch(
+            &leaves[5],
+            &branch[1..],
+            gindex,
+            &root
+        ));
+    }
+
+    #[test]
+    fn deep_normalized_branches() {
+        let depth = 40;
+        let leaves = test_chunks(8);
+        let root = merkleize(&leaves, Some(1 << depth));
+        let branch = merkle_branch(&leaves, depth, 5);
+
+        let gindex = GeneralizedIndex::from_depth_and_index(depth as u32, 5).unwrap();
+        assert!(is_valid_normalized_merkle_branch(
+            &leaves[5], &branch, gindex, &root
+        ));
+
+        // The same leaf position modulo 2^32, which a 32-bit index would confuse with 5.
+        let gindex = GeneralizedIndex::from_depth_and_index(depth as u32, (1 << 35) + 5).unwrap();
+        assert!(!is_valid_normalized_merkle_branch(
+            &leaves[5], &branch, gindex, &root
+        ));
+    }
+
+    #[test]
+    fn zero_depth_branch() {
+        let leaf = [7; HASH_LEN];
+        assert!(merkle_branch(&[leaf], 0, 0).is_empty());
+        assert!(is_valid_merkle_branch(&leaf, &[], 0, 0, &leaf));
+    }
+
+    #[test]
+    #[should_panic]
+    fn branch_index_out_of_range() {
+        merkle_branch(&test_chunks(2), 1, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn exceeds_limit() {
+        merkleize(&test_chunks(3), Some(2));
+    }
+}
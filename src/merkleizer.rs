@@ -0,0 +1,159 @@
+//! Streaming Merkleization with memory bounded by the depth of the tree.
+use crate::merkle::tree_depth;
+use crate::{hash32_concat, zero_hash, HASH_LEN};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Error returned when writing more chunks to a `Merkleizer` than its limit allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    pub limit: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "more than {} chunks written", self.limit)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LimitExceeded {}
+
+/// Computes the Merkle root of chunks written one at a time.
+///
+/// Only one pending node is kept per level of the tree, so lists with very large limits and
+/// millions of elements can be hashed without holding the chunks or intermediate levels in
+/// memory. The result is identical to `merkleize` called with the same chunks and limit.
+#[derive(Debug, Clone)]
+pub struct Merkleizer {
+    /// The left sibling awaiting a right sibling at each level, if bit `level` of `count` is set.
+    ///
+    /// The last entry holds the root once the tree is full.
+    pending: Vec<[u8; HASH_LEN]>,
+    count: usize,
+    limit: usize,
+    depth: usize,
+    /// Level of the written nodes in the full tree, which determines the zero hashes used as
+    /// padding.
+    leaf_level: usize,
+}
+
+impl Merkleizer {
+    /// Create a `Merkleizer` for a tree with room for `limit` chunks.
+    pub fn new(limit: usize) -> Self {
+        Self::with_leaf_level(limit, 0)
+    }
+
+    /// Create a `Merkleizer` for the upper part of a tree, from `limit` nodes at `leaf_level`.
+    pub(crate) fn with_leaf_level(limit: usize, leaf_level: usize) -> Self {
+        let depth = tree_depth(limit);
+        Self {
+            pending: vec![[0; HASH_LEN]; depth + 1],
+            count: 0,
+            limit,
+            depth,
+            leaf_level,
+        }
+    }
+
+    /// Number of chunks written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Add the next chunk to the tree.
+    pub fn write(&mut self, chunk: [u8; HASH_LEN]) -> Result<(), LimitExceeded> {
+        if self.count >= self.limit {
+            return Err(LimitExceeded { limit: self.limit });
+        }
+
+        // Merge with every complete left subtree that this chunk completes.
+        let mut node = chunk;
+        let mut level = 0;
+        while (self.count >> level) & 1 == 1 {
+            node = hash32_concat(&self.pending[level], &node);
+            level += 1;
+        }
+        self.pending[level] = node;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Add every chunk yielded by `chunks` to the tree.
+    pub fn write_chunks(
+        &mut self,
+        chunks: impl IntoIterator<Item = [u8; HASH_LEN]>,
+    ) -> Result<(), LimitExceeded> {
+        chunks.into_iter().try_for_each(|chunk| self.write(chunk))
+    }
+
+    /// Compute the root of the tree, padding the remaining leaves with zero chunks.
+    pub fn finish(&self) -> [u8; HASH_LEN] {
+        if self.count.checked_shr(self.depth as u32) == Some(1) {
+            return self.pending[self.depth];
+        }
+
+        // Fold the pending nodes bottom-up, with zero subtrees to the right of the last chunk.
+        let mut root: Option<[u8; HASH_LEN]> = None;
+        for level in 0..self.depth {
+            let zero_hash = zero_hash(self.leaf_level + level);
+            root = if (self.count >> level) & 1 == 1 {
+                Some(hash32_concat(
+                    &self.pending[level],
+                    root.as_ref().unwrap_or(&zero_hash),
+                ))
+            } else {
+                root.map(|root| hash32_concat(&root, &zero_hash))
+            };
+        }
+
+        root.unwrap_or_else(|| zero_hash(self.leaf_level + self.depth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::test_chunks;
+    use crate::merkleize;
+
+    #[test]
+    fn matches_merkleize() {
+        for count in 0..=33 {
+            let chunks = test_chunks(count);
+            for limit in [count, count + 1, 64, 1 << 40, usize::MAX] {
+                let mut merkleizer = Merkleizer::new(limit);
+                merkleizer.write_chunks(chunks.iter().copied()).unwrap();
+                assert_eq!(merkleizer.count(), count);
+                assert_eq!(
+                    merkleizer.finish(),
+                    merkleize(&chunks, Some(limit)),
+                    "count {count} limit {limit}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn limit_exceeded() {
+        let mut merkleizer = Merkleizer::new(3);
+        merkleizer.write_chunks(test_chunks(3)).unwrap();
+        assert_eq!(
+            merkleizer.write([0; HASH_LEN]),
+            Err(LimitExceeded { limit: 3 })
+        );
+        assert_eq!(
+            LimitExceeded { limit: 3 }.to_string(),
+            "more than 3 chunks written"
+        );
+
+        let mut merkleizer = Merkleizer::new(0);
+        assert_eq!(
+            merkleizer.write([0; HASH_LEN]),
+            Err(LimitExceeded { limit: 0 })
+        );
+        assert_eq!(merkleizer.finish(), [0; HASH_LEN]);
+    }
+}
@@ -0,0 +1,82 @@
+//! Hashing many messages that share a fixed prefix.
+use crate::portable_impl::Engine;
+use crate::{compress256, hash_64, HASH_LEN};
+
+/// The SHA256 state after absorbing a fixed prefix, from which messages starting with that
+/// prefix can be hashed without absorbing it again.
+///
+/// Every full 64-byte block of the prefix is compressed once, in `new`, with `compress256`, so
+/// only prefixes of at least 64 bytes save any work. A shorter prefix stays buffered and is
+/// compressed again with every message, making this slower than hashing the concatenated message
+/// with `hash_fixed`. The exception is a 32-byte prefix followed by a 32-byte suffix, such as a
+/// left child hashed with many right siblings, which is hashed with `hash_64` like
+/// `hash32_concat`.
+#[derive(Clone)]
+pub struct Midstate {
+    engine: Engine,
+}
+
+impl Midstate {
+    /// Absorb `prefix`.
+    pub fn new(prefix: &[u8]) -> Self {
+        let mut engine = Engine::new();
+        engine.update(prefix, compress256);
+        Self { engine }
+    }
+
+    /// The digest of `prefix || suffix`.
+    pub fn hash(&self, suffix: &[u8]) -> [u8; HASH_LEN] {
+        match self.engine.single_block(suffix) {
+            Some(block) => hash_64(&block),
+            None => self.hash_parts(&[suffix]),
+        }
+    }
+
+    /// The digest of `prefix || parts[0] || parts[1] || ...`, without concatenating the parts.
+    pub fn hash_parts(&self, parts: &[&[u8]]) -> [u8; HASH_LEN] {
+        let mut out = [0; HASH_LEN];
+        self.hash_parts_into(parts, &mut out);
+        out
+    }
+
+    /// Like `hash_parts`, but writing the digest to `out`.
+    pub fn hash_parts_into(&self, parts: &[&[u8]], out: &mut [u8; HASH_LEN]) {
+        let mut engine = self.engine.clone();
+        for part in parts {
+            engine.update(part, compress256);
+        }
+        *out = engine.finalize(compress256);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_fixed;
+
+    #[test]
+    fn matches_full_hash() {
+        for prefix_len in [0, 32, 63, 64, 65, 200] {
+            let prefix: Vec<u8> = (0..prefix_len).map(|i| i as u8).collect();
+            let midstate = Midstate::new(&prefix);
+
+            for suffix in [&b""[..], &[1], &[2; 5], &[3; 32], &[4; 64], &[5; 100]] {
+                let expected = hash_fixed(&[&prefix, suffix].concat());
+                assert_eq!(midstate.hash(suffix), expected, "{prefix_len}");
+                assert_eq!(midstate.hash_parts(&[&suffix[..0], suffix]), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_round_position() {
+        let seed = [42; HASH_LEN];
+        let midstate = Midstate::new(&seed);
+        let round = [7u8];
+        let position = 1234u32.to_le_bytes();
+        assert_eq!(
+            midstate.hash_parts(&[&round, &position]),
+            hash_fixed(&[&seed[..], &round, &position].concat())
+        );
+    }
+}
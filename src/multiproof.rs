@@ -0,0 +1,337 @@
+//! Merkle multiproofs over generalized indices, as defined by the SSZ `merkle-proofs.md`
+//! specification.
+use crate::{hash32_concat, GeneralizedIndex, HASH_LEN};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "zero_hash_cache")]
+use crate::merkle::{merkleize, tree_depth};
+
+/// Error returned when a multiproof is malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiproofError {
+    /// The number of leaves doesn't match the number of indices.
+    LeafCountMismatch { leaves: usize, indices: usize },
+    /// The number of proof nodes doesn't match the number of helper indices.
+    ProofLengthMismatch { expected: usize, found: usize },
+    /// The leaves and proof nodes are not sufficient to compute the root.
+    MissingRoot,
+    /// A proof node is the root of a subtree with `2^depth` leaves, too many to index.
+    SubtreeTooDeep { depth: usize },
+}
+
+impl fmt::Display for MultiproofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LeafCountMismatch { leaves, indices } => {
+                write!(f, "{leaves} leaves given for {indices} indices")
+            }
+            Self::ProofLengthMismatch { expected, found } => {
+                write!(f, "expected {expected} proof nodes, found {found}")
+            }
+            Self::MissingRoot => f.write_str("leaves and proof don't determine the root"),
+            Self::SubtreeTooDeep { depth } => {
+                write!(f, "subtree of depth {depth} is too deep to compute")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MultiproofError {}
+
+/// Generalized indices of the helper nodes needed to prove all of `indices` at once.
+///
+/// Nodes that can be computed from the proven leaves are excluded, so shared upper nodes appear
+/// only once. The result is sorted in decreasing order, which is the order in which the proof
+/// nodes must be supplied to `calculate_multi_merkle_root`.
+///
+/// This matches the `get_helper_indices` function from the SSZ specification.
+pub fn get_helper_indices(indices: &[GeneralizedIndex]) -> Vec<GeneralizedIndex> {
+    let mut helper_indices = BTreeSet::new();
+    let mut path_indices = BTreeSet::new();
+    for &index in indices {
+        helper_indices.extend(index.branch());
+        path_indices.extend(index.path_to_root());
+    }
+
+    helper_indices.retain(|index| !path_indices.contains(index));
+    helper_indices.into_iter().rev().collect()
+}
+
+/// Compute the root of a Merkle tree from `leaves` at generalized `indices` and the `proof` nodes
+/// at the corresponding helper indices.
+///
+/// This matches the `calculate_multi_merkle_root` function from the SSZ specification.
+pub fn calculate_multi_merkle_root(
+    leaves: &[[u8; HASH_LEN]],
+    proof: &[[u8; HASH_LEN]],
+    indices: &[GeneralizedIndex],
+) -> Result<[u8; HASH_LEN], MultiproofError> {
+    if leaves.len() != indices.len() {
+        return Err(MultiproofError::LeafCountMismatch {
+            leaves: leaves.len(),
+            indices: indices.len(),
+        });
+    }
+    let helper_indices = get_helper_indices(indices);
+    if proof.len() != helper_indices.len() {
+        return Err(MultiproofError::ProofLengthMismatch {
+            expected: helper_indices.len(),
+            found: proof.len(),
+        });
+    }
+
+    let mut objects: BTreeMap<GeneralizedIndex, [u8; HASH_LEN]> = indices
+        .iter()
+        .copied()
+        .zip(leaves.iter().copied())
+        .chain(helper_indices.into_iter().zip(proof.iter().copied()))
+        .collect();
+
+    // Process nodes deepest-first, adding each computed parent to the end of the queue.
+    let mut keys: Vec<GeneralizedIndex> = objects.keys().rev().copied().collect();
+    let mut pos = 0;
+    while let Some(&index) = keys.get(pos) {
+        pos += 1;
+        let (Some(parent), Some(sibling)) = (index.parent(), index.sibling()) else {
+            continue;
+        };
+        if objects.contains_key(&parent) {
+            continue;
+        }
+        if let (Some(node), Some(sibling_node)) = (objects.get(&index), objects.get(&sibling)) {
+            let node = if index.is_right() {
+                hash32_concat(sibling_node, node)
+            } else {
+                hash32_concat(node, sibling_node)
+            };
+            objects.insert(parent, node);
+            keys.push(parent);
+        }
+    }
+
+    objects
+        .get(&GeneralizedIndex::ROOT)
+        .copied()
+        .ok_or(MultiproofError::MissingRoot)
+}
+
+/// Check that `leaves` at generalized `indices` are included in the tree with the given `root`.
+///
+/// Returns `false` if the multiproof is malformed.
+///
+/// This matches the `verify_merkle_multiproof` function from the SSZ specification.
+pub fn verify_merkle_multiproof(
+    leaves: &[[u8; HASH_LEN]],
+    proof: &[[u8; HASH_LEN]],
+    indices: &[GeneralizedIndex],
+    root: &[u8; HASH_LEN],
+) -> bool {
+    calculate_multi_merkle_root(leaves, proof, indices).is_ok_and(|computed| computed == *root)
+}
+
+/// Compute the multiproof for `indices` in the tree formed by `leaves`, padded with zero chunks to
+/// `2^depth` leaves.
+///
+/// The proof nodes are returned in the order of `get_helper_indices(indices)`.
+///
+/// Returns `MultiproofError::SubtreeTooDeep` if a proof node is the root of a subtree with more
+/// than `usize::MAX` leaves.
+///
+/// # Panics
+///
+/// Panics if `leaves` doesn't fit in a tree of the given `depth`, or if any index is deeper than
+/// `depth`.
+#[cfg(feature = "zero_hash_cache")]
+pub fn merkle_multiproof(
+    leaves: &[[u8; HASH_LEN]],
+    depth: usize,
+    indices: &[GeneralizedIndex],
+) -> Result<Vec<[u8; HASH_LEN]>, MultiproofError> {
+    assert!(
+        tree_depth(leaves.len()) <= depth,
+        "{} leaves don't fit in a tree of depth {depth}",
+        leaves.len()
+    );
+    get_helper_indices(indices)
+        .into_iter()
+        .map(|index| merkle_node(leaves, depth, index))
+        .collect()
+}
+
+/// Compute the node at generalized `index` in the tree formed by `leaves`, padded with zero
+/// chunks to `2^depth` leaves.
+#[cfg(feature = "zero_hash_cache")]
+fn merkle_node(
+    leaves: &[[u8; HASH_LEN]],
+    depth: usize,
+    index: GeneralizedIndex,
+) -> Result<[u8; HASH_LEN], MultiproofError> {
+    let node_depth = index.depth() as usize;
+    assert!(
+        node_depth <= depth,
+        "generalized index {index} is deeper than the tree"
+    );
+
+    let subtree_depth = depth - node_depth;
+    let subtree_leaves = u32::try_from(subtree_depth)
+        .ok()
+        .and_then(|shift| 1usize.checked_shl(shift))
+        .ok_or(MultiproofError::SubtreeTooDeep {
+            depth: subtree_depth,
+        })?;
+    // A subtree starting beyond `usize::MAX` contains none of the leaves.
+    let start = usize::try_from(index.subtree_index())
+        .ok()
+        .and_then(|position| position.checked_mul(subtree_leaves))
+        .map_or(leaves.len(), |start| start.min(leaves.len()));
+    let end = leaves.len().min(start.saturating_add(subtree_leaves));
+    Ok(merkleize(&leaves[start..end], Some(subtree_leaves)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gindices(indices: &[u64]) -> Vec<GeneralizedIndex> {
+        indices
+            .iter()
+            .map(|&index| GeneralizedIndex::new(index).unwrap())
+            .collect()
+    }
+
+    fn helper_indices(indices: &[u64]) -> Vec<u64> {
+        get_helper_indices(&gindices(indices))
+            .into_iter()
+            .map(u64::from)
+            .collect()
+    }
+
+    #[test]
+    fn helper_indices_single_leaf() {
+        // A single leaf needs its full branch, from the bottom up.
+        assert_eq!(helper_indices(&[8]), vec![9, 5, 3]);
+        assert_eq!(helper_indices(&[1]), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn helper_indices_shared_nodes() {
+        // Siblings need no helper at their own level and share everything above it.
+        assert_eq!(helper_indices(&[8, 9]), vec![5, 3]);
+        assert_eq!(helper_indices(&[8, 10]), vec![11, 9, 3]);
+        assert_eq!(helper_indices(&[8, 15]), vec![14, 9, 6, 5]);
+        assert_eq!(helper_indices(&[4, 5, 6, 7]), Vec::<u64>::new());
+    }
+
+    #[cfg(feature = "zero_hash_cache")]
+    mod with_tree {
+        use super::*;
+        use crate::merkle::{merkleize, test_chunks};
+
+        #[test]
+        fn multiproofs_are_valid() {
+            let depth = 3;
+            let leaves = test_chunks(6);
+            let root = merkleize(&leaves, Some(1 << depth));
+
+            let index_sets: &[&[u64]] = &[
+                &[8],
+                &[15],
+                &[8, 9],
+                &[8, 13, 14],
+                &[2, 13],
+                &[4, 5, 6, 7],
+                &[1],
+            ];
+            for &indices in index_sets {
+                let indices = &gindices(indices);
+                let proof = merkle_multiproof(&leaves, depth, indices).unwrap();
+                let proven: Vec<_> = indices
+                    .iter()
+                    .map(|&index| merkle_node(&leaves, depth, index).unwrap())
+                    .collect();
+                assert!(
+                    verify_merkle_multiproof(&proven, &proof, indices, &root),
+                    "{indices:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn invalid_multiproofs() {
+            let depth = 3;
+            let leaves = test_chunks(8);
+            let root = merkleize(&leaves, Some(1 << depth));
+            let indices = gindices(&[9, 12]);
+            let proven = [leaves[1], leaves[4]];
+            let mut proof = merkle_multiproof(&leaves, depth, &indices).unwrap();
+
+            assert_eq!(
+                calculate_multi_merkle_root(&proven, &proof, &indices),
+                Ok(root)
+            );
+            assert_eq!(
+                calculate_multi_merkle_root(&proven[..1], &proof, &indices),
+                Err(MultiproofError::LeafCountMismatch {
+                    leaves: 1,
+                    indices: 2
+                })
+            );
+            assert_eq!(
+                calculate_multi_merkle_root(&proven, &proof[1..], &indices),
+                Err(MultiproofError::ProofLengthMismatch {
+                    expected: proof.len(),
+                    found: proof.len() - 1
+                })
+            );
+            assert_eq!(
+                calculate_multi_merkle_root(&[], &[], &[]),
+                Err(MultiproofError::MissingRoot)
+            );
+
+            assert!(!verify_merkle_multiproof(
+                &[leaves[4], leaves[1]],
+                &proof,
+                &indices,
+                &root
+            ));
+            proof[0][0] ^= 1;
+            assert!(!verify_merkle_multiproof(&proven, &proof, &indices, &root));
+        }
+
+        #[test]
+        fn deep_trees() {
+            // The node at depth 10 is the root of the leftmost subtree, with `2^54` leaves, and the
+            // helper node at depth 1 has `2^63`.
+            let depth = 64;
+            let leaves = test_chunks(3);
+            let subtree_root = merkleize(&leaves, Some(1 << 54));
+            let mut root = subtree_root;
+            for level in 54..depth {
+                root = hash32_concat(&root, &crate::zero_hash(level));
+            }
+
+            let indices = gindices(&[1 << 10]);
+            let proof = merkle_multiproof(&leaves, depth, &indices).unwrap();
+            assert!(verify_merkle_multiproof(
+                &[subtree_root],
+                &proof,
+                &indices,
+                &root
+            ));
+
+            // A leaf past 32 bits of position, whose index must not wrap onto `leaves[1]`.
+            let index = GeneralizedIndex::from_depth_and_index(40, (1 << 32) + 1).unwrap();
+            assert_eq!(merkle_node(&leaves, 40, index), Ok([0; HASH_LEN]));
+
+            let err = merkle_multiproof(&leaves, 66, &gindices(&[2])).unwrap_err();
+            assert_eq!(err, MultiproofError::SubtreeTooDeep { depth: 65 });
+            assert_eq!(
+                err.to_string(),
+                "subtree of depth 65 is too deep to compute"
+            );
+        }
+    }
+}
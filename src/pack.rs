@@ -0,0 +1,201 @@
+//! Packing of basic SSZ values into 32-byte chunks.
+use crate::HASH_LEN;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+#[cfg(feature = "zero_hash_cache")]
+use crate::merkle::merkleize_chunks;
+
+/// A basic SSZ value, which is packed into chunks alongside other values of the same type.
+pub trait PackedValue {
+    /// Size of the value's serialization, which must divide `HASH_LEN`.
+    const SIZE: usize;
+
+    /// Write the little-endian serialization of the value to `out`, which is `SIZE` bytes long.
+    fn write_le(&self, out: &mut [u8]);
+}
+
+macro_rules! impl_packed_value_for_uint {
+    ($($type:ty),*) => {
+        $(
+            impl PackedValue for $type {
+                const SIZE: usize = core::mem::size_of::<$type>();
+
+                fn write_le(&self, out: &mut [u8]) {
+                    out.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_packed_value_for_uint!(u8, u16, u32, u64, u128);
+
+impl PackedValue for bool {
+    const SIZE: usize = 1;
+
+    fn write_le(&self, out: &mut [u8]) {
+        out[0] = *self as u8;
+    }
+}
+
+/// A 256-bit unsigned integer as little-endian 64-bit limbs.
+///
+/// This is the representation used by the `U256` types of `ethereum-types` and `ruint`.
+impl PackedValue for [u64; 4] {
+    const SIZE: usize = 32;
+
+    fn write_le(&self, out: &mut [u8]) {
+        for (out, limb) in out.chunks_exact_mut(8).zip(self) {
+            out.copy_from_slice(&limb.to_le_bytes());
+        }
+    }
+}
+
+/// Number of chunks needed to pack `count` values of type `T`.
+pub fn packed_chunk_count<T: PackedValue>(count: usize) -> usize {
+    count.div_ceil(HASH_LEN / T::SIZE)
+}
+
+/// Pack `values` into 32-byte chunks, right-padding the last chunk with zeros.
+///
+/// This matches the `pack` function from the SSZ specification.
+pub fn pack<T: PackedValue>(values: &[T]) -> Vec<[u8; HASH_LEN]> {
+    packed_chunks(values).collect()
+}
+
+/// Iterate over the chunks of `values` packed as by `pack`, without allocating.
+pub fn packed_chunks<T: PackedValue>(values: &[T]) -> PackedChunks<'_, T> {
+    PackedChunks {
+        values: values.chunks(HASH_LEN / T::SIZE),
+    }
+}
+
+/// Compute the Merkle root of `values` packed into chunks, without allocating the packed chunks.
+///
+/// The chunks are hashed as they are packed, keeping one pending node per level of the tree.
+///
+/// `limit` is the maximum number of values, such as `N` for an SSZ `List[T, N]`, and is converted
+/// to a chunk limit for `merkleize`.
+///
+/// # Panics
+///
+/// Panics if `values.len()` exceeds `limit`.
+#[cfg(feature = "zero_hash_cache")]
+pub fn merkleize_packed<T: PackedValue>(values: &[T], limit: Option<usize>) -> [u8; HASH_LEN] {
+    if let Some(limit) = limit {
+        assert!(
+            values.len() <= limit,
+            "number of values ({}) exceeds limit ({limit})",
+            values.len()
+        );
+    }
+    merkleize_chunks(packed_chunks(values), limit.map(packed_chunk_count::<T>))
+}
+
+/// Iterator over the 32-byte chunks of packed basic values.
+///
+/// Created by `packed_chunks`.
+#[derive(Debug, Clone)]
+pub struct PackedChunks<'a, T> {
+    values: core::slice::Chunks<'a, T>,
+}
+
+impl<T: PackedValue> Iterator for PackedChunks<'_, T> {
+    type Item = [u8; HASH_LEN];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = self.values.next()?;
+        let mut chunk = [0; HASH_LEN];
+        for (out, value) in chunk.chunks_exact_mut(T::SIZE).zip(values) {
+            value.write_le(out);
+        }
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T: PackedValue> ExactSizeIterator for PackedChunks<'_, T> {}
+
+impl<T: PackedValue> FusedIterator for PackedChunks<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_uints() {
+        assert_eq!(pack::<u8>(&[]), Vec::<[u8; HASH_LEN]>::new());
+
+        let chunks = pack(&[0x0102u16, 0x0304, 0x0506]);
+        let mut expected = [0; HASH_LEN];
+        expected[..6].copy_from_slice(&[0x02, 0x01, 0x04, 0x03, 0x06, 0x05]);
+        assert_eq!(chunks, vec![expected]);
+
+        let values: Vec<u64> = (1..=5).collect();
+        let chunks = pack(&values);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0][..8], 1u64.to_le_bytes());
+        assert_eq!(chunks[0][24..], 4u64.to_le_bytes());
+        assert_eq!(chunks[1][..8], 5u64.to_le_bytes());
+        assert_eq!(chunks[1][8..], [0; 24]);
+
+        let chunks = pack(&[u128::MAX, 1, 2]);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0][..16], [0xff; 16]);
+        assert_eq!(chunks[1][..16], 2u128.to_le_bytes());
+    }
+
+    #[test]
+    fn pack_bools() {
+        let chunks = pack(&[true, false, true]);
+        let mut expected = [0; HASH_LEN];
+        expected[0] = 1;
+        expected[2] = 1;
+        assert_eq!(chunks, vec![expected]);
+    }
+
+    #[test]
+    fn pack_u256() {
+        let chunks = pack(&[[1, 2, 3, 4u64]]);
+        assert_eq!(chunks[0][..8], 1u64.to_le_bytes());
+        assert_eq!(chunks[0][24..], 4u64.to_le_bytes());
+    }
+
+    #[test]
+    fn chunk_counts() {
+        assert_eq!(packed_chunk_count::<u64>(0), 0);
+        assert_eq!(packed_chunk_count::<u64>(4), 1);
+        assert_eq!(packed_chunk_count::<u64>(5), 2);
+        assert_eq!(packed_chunk_count::<bool>(33), 2);
+        assert_eq!(packed_chunk_count::<[u64; 4]>(3), 3);
+        assert_eq!(packed_chunks(&[0u16; 17]).len(), 2);
+    }
+
+    #[cfg(feature = "zero_hash_cache")]
+    #[test]
+    fn merkleize_packed_matches_merkleize() {
+        use crate::merkleize;
+
+        for count in [0, 1, 4, 5, 31, 100, 1000] {
+            let values: Vec<u64> = (0..count).map(|i| i * 32_000_000_000).collect();
+            let chunks = pack(&values);
+            assert_eq!(merkleize_packed(&values, None), merkleize(&chunks, None));
+            assert_eq!(
+                merkleize_packed(&values, Some(1 << 40)),
+                merkleize(&chunks, Some(1 << 38))
+            );
+        }
+    }
+
+    #[cfg(feature = "zero_hash_cache")]
+    #[test]
+    #[should_panic]
+    fn merkleize_packed_exceeds_limit() {
+        // Five values fit in the two chunks allowed for three values, but must still be rejected.
+        merkleize_packed(&[0u64; 5], Some(3));
+    }
+}
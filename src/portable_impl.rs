@@ -0,0 +1,272 @@
+//! SHA256 in pure Rust, for targets where `ring` and `sha2` are unavailable or unwanted, and for
+//! hashing at compile time.
+use crate::compress::{compress_block, compress_portable, PADDING_BLOCK};
+use crate::{Sha256, Sha256Context, HASH_LEN, SHA256_IV};
+use alloc::vec::Vec;
+
+/// Portable implementation of SHA256, without intrinsics, assembly or C dependencies.
+pub struct PortableImpl;
+
+/// Streaming context for `PortableImpl`.
+#[derive(Clone)]
+pub struct PortableContext {
+    engine: Engine,
+}
+
+impl Sha256Context for PortableContext {
+    fn new() -> Self {
+        Self {
+            engine: Engine::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.engine.update(bytes, compress_portable);
+    }
+
+    fn finalize(self) -> [u8; HASH_LEN] {
+        self.engine.finalize(compress_portable)
+    }
+}
+
+/// The chaining state and buffered partial block of a message. Each context passes its
+/// implementation of the compression function to every call.
+#[derive(Clone)]
+pub(crate) struct Engine {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    /// Total length of the input, in bytes.
+    length: u64,
+}
+
+impl Engine {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: SHA256_IV,
+            buffer: [0; 64],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn update(
+        &mut self,
+        mut bytes: &[u8],
+        compress: impl Fn(&mut [u32; 8], &[[u8; 64]]),
+    ) {
+        self.length += bytes.len() as u64;
+
+        if self.buffer_len > 0 {
+            let take = bytes.len().min(64 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&bytes[..take]);
+            self.buffer_len += take;
+            bytes = &bytes[take..];
+            if self.buffer_len < 64 {
+                return;
+            }
+            compress(&mut self.state, &[self.buffer]);
+            self.buffer_len = 0;
+        }
+
+        let block_count = bytes.len() / 64;
+        // SAFETY: `[u8; 64]` has the alignment of `u8`, and `block_count` blocks fit in `bytes`.
+        let blocks: &[[u8; 64]] =
+            unsafe { core::slice::from_raw_parts(bytes.as_ptr().cast(), block_count) };
+        compress(&mut self.state, blocks);
+
+        let rest = &bytes[block_count * 64..];
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    /// The whole message as a single block, if nothing has been compressed yet and `suffix`
+    /// brings the message to exactly 64 bytes.
+    pub(crate) fn single_block(&self, suffix: &[u8]) -> Option<[u8; 64]> {
+        if self.length != self.buffer_len as u64 || self.buffer_len + suffix.len() != 64 {
+            return None;
+        }
+        let mut block = self.buffer;
+        block[self.buffer_len..].copy_from_slice(suffix);
+        Some(block)
+    }
+
+    #[inline(always)]
+    pub(crate) fn finalize(
+        mut self,
+        compress: impl Fn(&mut [u32; 8], &[[u8; 64]]),
+    ) -> [u8; HASH_LEN] {
+        let bit_length = self.length.wrapping_mul(8);
+
+        self.buffer[self.buffer_len..].fill(0);
+        self.buffer[self.buffer_len] = 0x80;
+        if self.buffer_len >= 56 {
+            compress(&mut self.state, &[self.buffer]);
+            self.buffer = [0; 64];
+        }
+        self.buffer[56..].copy_from_slice(&bit_length.to_be_bytes());
+        compress(&mut self.state, &[self.buffer]);
+
+        state_to_digest(self.state)
+    }
+}
+
+impl Sha256 for PortableImpl {
+    type Context = PortableContext;
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        self.hash_fixed(input).to_vec()
+    }
+
+    fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+        let mut ctxt = PortableContext::new();
+        ctxt.update(input);
+        ctxt.finalize()
+    }
+
+    fn hash_64(&self, input: &[u8; 64]) -> [u8; HASH_LEN] {
+        let state = compress_block(SHA256_IV, input);
+        state_to_digest(compress_block(state, &PADDING_BLOCK))
+    }
+}
+
+/// Hash `input` at compile time.
+///
+/// This is much slower than `hash_fixed` at runtime, but makes it possible to define constants
+/// such as the roots of empty lists.
+pub const fn hash_const(input: &[u8]) -> [u8; HASH_LEN] {
+    let mut state = SHA256_IV;
+    let mut block = [0; 64];
+
+    let mut offset = 0;
+    while offset + 64 <= input.len() {
+        let mut i = 0;
+        while i < 64 {
+            block[i] = input[offset + i];
+            i += 1;
+        }
+        state = compress_block(state, &block);
+        offset += 64;
+    }
+
+    // Copy the remaining bytes and pad them, which takes one more block if there's room for the
+    // length after the `0x80` byte and two otherwise.
+    let remaining = input.len() - offset;
+    let mut i = 0;
+    while i < 64 {
+        block[i] = if i < remaining { input[offset + i] } else { 0 };
+        i += 1;
+    }
+    block[remaining] = 0x80;
+    if remaining >= 56 {
+        state = compress_block(state, &block);
+        block = [0; 64];
+    }
+    let bit_length = (input.len() as u64).wrapping_mul(8).to_be_bytes();
+    let mut i = 0;
+    while i < 8 {
+        block[56 + i] = bit_length[i];
+        i += 1;
+    }
+    state_to_digest(compress_block(state, &block))
+}
+
+/// Hash two 32-byte inputs concatenated at compile time, like `hash32_concat`.
+pub const fn hash32_concat_const(h1: &[u8; HASH_LEN], h2: &[u8; HASH_LEN]) -> [u8; HASH_LEN] {
+    let mut block = [0; 64];
+    let mut i = 0;
+    while i < HASH_LEN {
+        block[i] = h1[i];
+        block[HASH_LEN + i] = h2[i];
+        i += 1;
+    }
+    let state = compress_block(SHA256_IV, &block);
+    state_to_digest(compress_block(state, &PADDING_BLOCK))
+}
+
+/// The big-endian encoding of a final state.
+pub(crate) const fn state_to_digest(state: [u32; 8]) -> [u8; HASH_LEN] {
+    let mut output = [0; HASH_LEN];
+    let mut i = 0;
+    while i < 8 {
+        let bytes = state[i].to_be_bytes();
+        output[4 * i] = bytes[0];
+        output[4 * i + 1] = bytes[1];
+        output[4 * i + 2] = bytes[2];
+        output[4 * i + 3] = bytes[3];
+        i += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_hex::FromHex;
+
+    #[test]
+    fn known_answers() {
+        let million_a = vec![b'a'; 1_000_000];
+        let vectors: [(&[u8], &str); 3] = [
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+            (
+                &million_a,
+                "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            ),
+        ];
+        for (input, expected) in vectors {
+            let expected: Vec<u8> = expected.from_hex().unwrap();
+            assert_eq!(PortableImpl.hash(input), expected);
+        }
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let input: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 300] {
+            let input = &input[..len];
+            let expected = crate::hash_fixed(input);
+            assert_eq!(PortableImpl.hash_fixed(input), expected, "{len}");
+
+            for chunk_size in [1, 7, 64, 100] {
+                let mut ctxt = PortableContext::new();
+                for chunk in input.chunks(chunk_size) {
+                    ctxt.update(chunk);
+                }
+                assert_eq!(ctxt.finalize(), expected, "{len} {chunk_size}");
+            }
+        }
+
+        let pair = [9; 64];
+        assert_eq!(PortableImpl.hash_64(&pair), crate::hash_fixed(&pair));
+    }
+
+    #[test]
+    fn const_hashing() {
+        const ABC: [u8; HASH_LEN] = hash_const(b"abc");
+        assert_eq!(ABC, crate::hash_fixed(b"abc"));
+
+        let input: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 128, 300] {
+            assert_eq!(
+                hash_const(&input[..len]),
+                crate::hash_fixed(&input[..len]),
+                "{len}"
+            );
+        }
+
+        let (h1, h2) = ([1; HASH_LEN], [2; HASH_LEN]);
+        assert_eq!(
+            hash32_concat_const(&h1, &h2),
+            crate::hash32_concat(&h1, &h2)
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Known-answer tests for the SHA256 backends.
+use crate::{Backend, PortableImpl, Sha256, Sha256Context, HASH_LEN};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "ring")]
+use crate::RingImpl;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::Sha2Impl;
+#[cfg(target_arch = "x86_64")]
+use crate::{Avx2Impl, Avx512Impl};
+
+/// Test vectors from FIPS 180-2, plus a 64-byte message as hashed by the batch implementations.
+const VECTORS: &[(&[u8], [u8; HASH_LEN])] = &[
+    (
+        b"",
+        digest("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+    ),
+    (
+        b"abc",
+        digest("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    ),
+    (
+        b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        digest("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+    ),
+    (
+        b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+        digest("cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"),
+    ),
+    (&[0; 64], ZERO_PAIR_DIGEST),
+];
+
+const ZERO_PAIR_DIGEST: [u8; HASH_LEN] =
+    digest("f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b");
+
+/// Enough pairs to fill every lane of the widest multi-buffer implementation, plus a remainder.
+const BATCH_SIZE: usize = 19;
+
+/// Error returned by `self_test` when backends compute incorrect digests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestError {
+    /// The backends that failed, which have been disabled.
+    ///
+    /// `Portable` is still used as a last resort if it fails, so a failure that includes it should
+    /// be treated as fatal.
+    pub failed: Vec<Backend>,
+}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SHA256 self-test failed for backends: ")?;
+        for (i, backend) in self.failed.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{backend}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SelfTestError {}
+
+/// Run every available backend against known SHA256 test vectors.
+///
+/// Backends that compute an incorrect digest are disabled, so that `DynamicImpl::best()` no
+/// longer selects them and `Backend::force` refuses them. This guards against a miscomputing
+/// code path, such as a SIMD unit affected by a bad microcode update or an emulator bug.
+///
+/// With the `lazy_self_test` feature this runs automatically before the first hash, and its result
+/// is available from `lazy_self_test_result`.
+pub fn self_test() -> Result<(), SelfTestError> {
+    let failed: Vec<Backend> = Backend::ALL
+        .into_iter()
+        .filter(|backend| backend.is_supported() && !check_backend(*backend))
+        .collect();
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+    for backend in &failed {
+        backend.disable();
+    }
+    Err(SelfTestError { failed })
+}
+
+#[cfg(feature = "lazy_self_test")]
+static LAZY_RESULT: std::sync::OnceLock<Result<(), SelfTestError>> = std::sync::OnceLock::new();
+
+/// Run `self_test` once, panicking if no correct backend remains.
+#[cfg(feature = "lazy_self_test")]
+pub(crate) fn lazy_self_test() {
+    if let Err(err) = LAZY_RESULT.get_or_init(self_test) {
+        assert!(
+            !err.failed.contains(&Backend::Portable),
+            "{err}, leaving no correct backend"
+        );
+    }
+}
+
+/// The result of the self-test run automatically before the first hash, or `None` if nothing has
+/// been hashed yet.
+///
+/// Failing backends have already been disabled; reporting the failure is left to the caller.
+#[cfg(feature = "lazy_self_test")]
+pub fn lazy_self_test_result() -> Option<&'static Result<(), SelfTestError>> {
+    LAZY_RESULT.get()
+}
+
+fn check_backend(backend: Backend) -> bool {
+    match backend {
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        Backend::Sha2 => check(&Sha2Impl),
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx512 => check(&Avx512Impl),
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => check(&Avx2Impl),
+        #[cfg(feature = "ring")]
+        Backend::Ring => check(&RingImpl),
+        Backend::Portable => check(&PortableImpl),
+        // Backends that aren't compiled in are never selected.
+        #[allow(unreachable_patterns)]
+        _ => true,
+    }
+}
+
+fn check<H: Sha256>(implementation: &H) -> bool {
+    let single = VECTORS.iter().all(|(input, expected)| {
+        let mut ctxt = H::Context::new();
+        for chunk in input.chunks(7) {
+            ctxt.update(chunk);
+        }
+        let mut out = [0; HASH_LEN];
+        implementation.hash_into(input, &mut out);
+        implementation.hash(input) == expected
+            && implementation.hash_fixed(input) == *expected
+            && out == *expected
+            && ctxt.finalize() == *expected
+    });
+
+    // Batches are checked against the known answer for zero pairs, and against the single-message
+    // path (verified above) for distinct pairs, which catches lanes being mixed up.
+    let mut output = [[0; HASH_LEN]; BATCH_SIZE];
+    implementation.hash32_concat_batch(&[[0; 64]; BATCH_SIZE], &mut output);
+    let zero_batch = output.iter().all(|digest| *digest == ZERO_PAIR_DIGEST);
+
+    let pairs: [[u8; 64]; BATCH_SIZE] =
+        core::array::from_fn(|i| core::array::from_fn(|j| (i * 64 + j) as u8));
+    implementation.hash32_concat_batch(&pairs, &mut output);
+    let batch = pairs.iter().zip(&output).all(|(pair, digest)| {
+        implementation.hash_fixed(pair) == *digest && implementation.hash_64(pair) == *digest
+    });
+
+    let single_block = implementation.hash_64(&[0; 64]) == ZERO_PAIR_DIGEST;
+
+    single && single_block && zero_batch && batch
+}
+
+/// Decode a hex digest at compile time.
+const fn digest(hex: &str) -> [u8; HASH_LEN] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("invalid hex digit"),
+        }
+    }
+
+    let hex = hex.as_bytes();
+    assert!(hex.len() == 2 * HASH_LEN);
+    let mut out = [0; HASH_LEN];
+    let mut i = 0;
+    while i < HASH_LEN {
+        out[i] = (nibble(hex[2 * i]) << 4) | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend that flips a bit of every digest.
+    struct BrokenImpl;
+
+    impl Sha256 for BrokenImpl {
+        type Context = crate::PortableContext;
+
+        fn hash(&self, input: &[u8]) -> Vec<u8> {
+            self.hash_fixed(input).to_vec()
+        }
+
+        fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+            let mut digest = PortableImpl.hash_fixed(input);
+            digest[31] ^= 1;
+            digest
+        }
+    }
+
+    /// A backend whose batches swap the outputs of the first two lanes.
+    struct SwappedLanesImpl;
+
+    impl Sha256 for SwappedLanesImpl {
+        type Context = crate::PortableContext;
+
+        fn hash(&self, input: &[u8]) -> Vec<u8> {
+            PortableImpl.hash(input)
+        }
+
+        fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
+            PortableImpl.hash_fixed(input)
+        }
+
+        fn hash32_concat_batch(&self, pairs: &[[u8; 64]], output: &mut [[u8; HASH_LEN]]) {
+            PortableImpl.hash32_concat_batch(pairs, output);
+            output.swap(0, 1);
+        }
+    }
+
+    #[test]
+    fn backends_pass() {
+        assert_eq!(self_test(), Ok(()));
+        for backend in Backend::ALL {
+            assert!(!backend.is_disabled(), "{backend}");
+        }
+    }
+
+    #[test]
+    fn broken_backends_fail() {
+        assert!(check(&PortableImpl));
+        assert!(!check(&BrokenImpl));
+        assert!(!check(&SwappedLanesImpl));
+    }
+
+    #[cfg(feature = "lazy_self_test")]
+    #[test]
+    fn lazy_result_is_stored() {
+        crate::hash(b"abc");
+        assert_eq!(lazy_self_test_result(), Some(&Ok(())));
+    }
+
+    #[test]
+    fn error_names_backends() {
+        let err = SelfTestError {
+            failed: vec![Backend::Sha2, Backend::Avx2],
+        };
+        assert_eq!(
+            err.to_string(),
+            "SHA256 self-test failed for backends: sha2, avx2"
+        );
+    }
+}
@@ -1,11 +1,16 @@
 // This implementation should only be compiled on x86_64 due to its dependency on the `sha2` and
-// `cpufeatures` crates which do not compile on some architectures like RISC-V.
+// `cpufeatures` crates which do not compile on some architectures like RISC-V. On aarch64, `sha2`
+// only uses the SHA2 crypto extensions with its assembly, so `Armv8Sha2Impl` is used instead.
 #![cfg(target_arch = "x86_64")]
 
-use crate::{Sha256, Sha256Context, HASH_LEN};
+use crate::compress::PADDING_BLOCK;
+use crate::portable_impl::state_to_digest;
+use crate::{Sha256, Sha256Context, HASH_LEN, SHA256_IV};
+use alloc::vec::Vec;
+use sha2::digest::{consts::U64, generic_array::GenericArray};
 use sha2::Digest;
 
-/// Implementation of SHA256 using the `sha2` crate (fastest on x86_64 CPUs with SHA extensions).
+/// Implementation of SHA256 using the `sha2` crate (fastest on CPUs with SHA extensions).
 pub struct Sha2CrateImpl;
 
 impl Sha256Context for sha2::Sha256 {
@@ -20,6 +25,18 @@ impl Sha256Context for sha2::Sha256 {
     fn finalize(self) -> [u8; HASH_LEN] {
         sha2::Digest::finalize(self).into()
     }
+
+    fn finalize_into(self, out: &mut [u8; HASH_LEN]) {
+        sha2::Digest::finalize_into(self, out.into())
+    }
+
+    fn reset(&mut self) {
+        sha2::Digest::reset(self)
+    }
+
+    fn finalize_reset(&mut self) -> [u8; HASH_LEN] {
+        sha2::Digest::finalize_reset(self).into()
+    }
 }
 
 impl Sha256 for Sha2CrateImpl {
@@ -32,4 +49,30 @@ impl Sha256 for Sha2CrateImpl {
     fn hash_fixed(&self, input: &[u8]) -> [u8; HASH_LEN] {
         Self::Context::digest(input).into()
     }
+
+    fn hash_into(&self, input: &[u8], out: &mut [u8; HASH_LEN]) {
+        let mut ctxt = <Self::Context as sha2::Digest>::new();
+        sha2::Digest::update(&mut ctxt, input);
+        sha2::Digest::finalize_into(ctxt, out.into())
+    }
+
+    /// Compress the message and the precomputed padding block directly, bypassing the buffering
+    /// of the `sha2::Sha256` context.
+    fn hash_64(&self, input: &[u8; 64]) -> [u8; HASH_LEN] {
+        let mut state = SHA256_IV;
+        compress(&mut state, &[*input, PADDING_BLOCK]);
+        state_to_digest(state)
+    }
+}
+
+/// Apply the compression function of the `sha2` crate to `state` for each block in `blocks`.
+pub(crate) fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    // SAFETY: `GenericArray<u8, U64>` has the same size and alignment as `[u8; 64]`.
+    let blocks = unsafe {
+        core::slice::from_raw_parts(
+            blocks.as_ptr().cast::<GenericArray<u8, U64>>(),
+            blocks.len(),
+        )
+    };
+    sha2::compress256(state, blocks);
 }
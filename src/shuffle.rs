@@ -0,0 +1,209 @@
+//! The swap-or-not shuffle used to compute committees and proposers.
+use crate::{hash_fixed, HASH_LEN};
+use alloc::vec::Vec;
+
+/// Largest list that can be shuffled: `position / 256` is hashed as a `uint32`.
+const MAX_LIST_SIZE: u64 = 1 << 40;
+
+/// Length of `seed || round || position / 256`.
+const SOURCE_INPUT_LEN: usize = HASH_LEN + 1 + 4;
+
+/// The hashes for one round of the shuffle, from a buffer holding the seed and round, whose last
+/// four bytes are overwritten with each position block.
+struct RoundHasher {
+    buffer: [u8; SOURCE_INPUT_LEN],
+}
+
+impl RoundHasher {
+    fn new(seed: &[u8; HASH_LEN], round: u8) -> Self {
+        let mut buffer = [0; SOURCE_INPUT_LEN];
+        buffer[..HASH_LEN].copy_from_slice(seed);
+        buffer[HASH_LEN] = round;
+        Self { buffer }
+    }
+
+    /// The pivot, from `hash(seed || round)`.
+    fn pivot(&self, list_size: usize) -> usize {
+        let digest = hash_fixed(&self.buffer[..HASH_LEN + 1]);
+        let pivot = u64::from_le_bytes(digest[..8].try_into().unwrap()) % list_size as u64;
+        pivot as usize
+    }
+
+    /// The source of the swap bits for the 256 positions starting at `position & !0xff`, from
+    /// `hash(seed || round || position / 256)`.
+    fn source(&mut self, position: usize) -> [u8; HASH_LEN] {
+        let block = ((position >> 8) as u32).to_le_bytes();
+        self.buffer[HASH_LEN + 1..].copy_from_slice(&block);
+        hash_fixed(&self.buffer)
+    }
+}
+
+/// The bit of `source` that decides whether to swap the pair whose larger position is `position`.
+fn swap_bit(source: &[u8; HASH_LEN], position: usize) -> bool {
+    (source[(position & 0xff) >> 3] >> (position & 0x07)) & 1 == 1
+}
+
+/// Compute the position that `index` is moved to by `rounds` rounds of the swap-or-not shuffle
+/// of a list of `list_size` elements.
+///
+/// Returns `None` if `index` isn't less than `list_size`, or if `list_size` exceeds `2^40`.
+///
+/// This matches the `compute_shuffled_index` function from the consensus specification, where
+/// `rounds` is `SHUFFLE_ROUND_COUNT` (90 on mainnet). To shuffle a whole list, `shuffle_list` is
+/// much faster.
+pub fn compute_shuffled_index(
+    index: usize,
+    list_size: usize,
+    seed: &[u8; HASH_LEN],
+    rounds: u8,
+) -> Option<usize> {
+    if index >= list_size || list_size as u64 > MAX_LIST_SIZE {
+        return None;
+    }
+
+    let mut index = index;
+    for round in 0..rounds {
+        let mut hasher = RoundHasher::new(seed, round);
+        let pivot = hasher.pivot(list_size);
+        let flip = (pivot + list_size - index) % list_size;
+        let position = index.max(flip);
+        if swap_bit(&hasher.source(position), position) {
+            index = flip;
+        }
+    }
+    Some(index)
+}
+
+/// Shuffle `input` with `rounds` rounds of the swap-or-not shuffle.
+///
+/// With `forwards` the rounds are applied in order, which moves the element at `i` to
+/// `compute_shuffled_index(i, ..)`. Otherwise they're applied in reverse, which computes the
+/// inverse permutation: the element at `i` of the output is taken from
+/// `compute_shuffled_index(i, ..)` of the input, as when computing committees.
+///
+/// Rather than shuffling each index separately, every round swaps the mirrored pairs around the
+/// pivot and its complement, so each `hash(seed || round || position / 256)` is only computed
+/// once per round.
+///
+/// Returns `None` if `input` has more than `2^40` elements.
+pub fn shuffle_list<T>(
+    mut input: Vec<T>,
+    rounds: u8,
+    seed: &[u8; HASH_LEN],
+    forwards: bool,
+) -> Option<Vec<T>> {
+    let list_size = input.len();
+    if list_size as u64 > MAX_LIST_SIZE {
+        return None;
+    }
+    if list_size <= 1 || rounds == 0 {
+        return Some(input);
+    }
+
+    let mut round_order = 0..rounds;
+    let mut next_round = || {
+        if forwards {
+            round_order.next()
+        } else {
+            round_order.next_back()
+        }
+    };
+
+    while let Some(round) = next_round() {
+        let mut hasher = RoundHasher::new(seed, round);
+        let pivot = hasher.pivot(list_size);
+
+        // Pairs `(i, pivot - i)`, which swap when the bit at `pivot - i` is set, then pairs
+        // `(i, list_size - 1 - (i - pivot - 1))` to the right of the pivot, which swap when the
+        // bit at the larger position is set. In both halves the larger position counts down, so
+        // a new source is needed when it crosses into the previous block of 256 positions.
+        let mut swap_mirrored = |lower: core::ops::Range<usize>, mut upper: usize| {
+            let mut source = hasher.source(upper);
+            for i in lower {
+                if upper & 0xff == 0xff {
+                    source = hasher.source(upper);
+                }
+                if swap_bit(&source, upper) {
+                    input.swap(i, upper);
+                }
+                upper -= 1;
+            }
+        };
+        swap_mirrored(0..pivot.div_ceil(2), pivot);
+        swap_mirrored(pivot + 1..(pivot + list_size).div_ceil(2), list_size - 1);
+    }
+
+    Some(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `compute_shuffled_index` written as in the specification, hashing concatenated inputs.
+    fn reference_shuffled_index(
+        mut index: usize,
+        list_size: usize,
+        seed: &[u8],
+        rounds: u8,
+    ) -> usize {
+        for round in 0..rounds {
+            let pivot_hash = hash_fixed(&[seed, &[round]].concat());
+            let pivot = (u64::from_le_bytes(pivot_hash[..8].try_into().unwrap()) % list_size as u64)
+                as usize;
+            let flip = (pivot + list_size - index) % list_size;
+            let position = index.max(flip);
+            let source =
+                hash_fixed(&[seed, &[round], &((position / 256) as u32).to_le_bytes()].concat());
+            let byte = source[(position % 256) / 8];
+            if (byte >> (position % 8)) % 2 == 1 {
+                index = flip;
+            }
+        }
+        index
+    }
+
+    #[test]
+    fn shuffled_index_matches_reference() {
+        let seed = [0x5a; HASH_LEN];
+        for list_size in [1usize, 2, 3, 100, 257, 1000] {
+            for index in (0..list_size).step_by(list_size.div_ceil(20)) {
+                assert_eq!(
+                    compute_shuffled_index(index, list_size, &seed, 90),
+                    Some(reference_shuffled_index(index, list_size, &seed, 90)),
+                    "{index} {list_size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shuffle_list_matches_shuffled_index() {
+        let rounds = 10;
+        for list_size in [0, 1, 2, 3, 255, 256, 257, 600, 1024] {
+            let seed = hash_fixed(&(list_size as u64).to_le_bytes());
+            let input: Vec<usize> = (0..list_size).collect();
+            let shuffled: Vec<usize> = (0..list_size)
+                .map(|i| compute_shuffled_index(i, list_size, &seed, rounds).unwrap())
+                .collect();
+
+            let forwards = shuffle_list(input.clone(), rounds, &seed, true).unwrap();
+            let backwards = shuffle_list(input.clone(), rounds, &seed, false).unwrap();
+            for i in 0..list_size {
+                assert_eq!(forwards[shuffled[i]], i, "{list_size}");
+                assert_eq!(backwards[i], shuffled[i], "{list_size}");
+            }
+            assert_eq!(shuffle_list(forwards, rounds, &seed, false).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert_eq!(compute_shuffled_index(0, 0, &[0; HASH_LEN], 90), None);
+        assert_eq!(compute_shuffled_index(5, 5, &[0; HASH_LEN], 90), None);
+        assert_eq!(
+            shuffle_list(vec![3, 1, 2], 0, &[0; HASH_LEN], true),
+            Some(vec![3, 1, 2])
+        );
+    }
+}